@@ -0,0 +1,360 @@
+use std::path::{Path, PathBuf};
+
+use tokio::fs::{copy, read, remove_file};
+use tracing::{span, Span};
+
+use crate::action::base::create_file::sha256_hex;
+use crate::action::{
+    Action, ActionDescription, ActionError, ActionErrorKind, ActionTag, StatefulAction,
+};
+use crate::planner::ShellProfileLocations;
+use crate::self_test::Shell;
+
+const BACKUP_SUFFIX: &str = "nix-installer-backup";
+
+/** Back up the profile files of a [`Shell`] to a `.nix-installer-backup` sibling before
+they are modified, restoring them on [`revert`](BackupShellProfile::revert).
+
+A profile is only restored if, once the Nix fragment was removed again, it still is what was backed up.
+Otherwise it was edited since, such as by a distribution update, so it is kept and so is the backup.
+
+Shells which are not present are skipped. So are profiles which already have a backup, such as one
+left by an earlier install, which is kept as is.
+ */
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone)]
+pub struct BackupShellProfile {
+    shell: Shell,
+    profiles: Vec<PathBuf>,
+    /// The profiles which were actually backed up during execute
+    backed_up: Vec<BackedUpProfile>,
+}
+
+/// A profile backed up by [`BackupShellProfile`]
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, PartialEq, Eq)]
+pub struct BackedUpProfile {
+    pub profile: PathBuf,
+    /// The SHA-256 of the profile when it was backed up, as lowercase hex
+    pub sha256: String,
+}
+
+impl BackupShellProfile {
+    #[tracing::instrument(level = "debug", skip_all)]
+    pub async fn plan(
+        shell: Shell,
+        locations: &ShellProfileLocations,
+    ) -> Result<StatefulAction<Self>, ActionError> {
+        Self::plan_if_installed(shell, shell.path().is_some(), locations).await
+    }
+
+    async fn plan_if_installed(
+        shell: Shell,
+        installed: bool,
+        locations: &ShellProfileLocations,
+    ) -> Result<StatefulAction<Self>, ActionError> {
+        let profiles = profiles_for(shell, locations)
+            .into_iter()
+            // Some tools (eg `nix-darwin`) create symlinks to these files, they are not ours to back up.
+            .filter(|profile| profile.is_file() && !profile.is_symlink())
+            .filter(|profile| {
+                let backup = backup_path(profile);
+                if backup.exists() {
+                    // Likely from before an earlier install, so it is the better one to keep
+                    tracing::debug!("Keeping the existing backup `{}`", backup.display());
+                    return false;
+                }
+                true
+            })
+            .collect::<Vec<_>>();
+
+        let this = Self {
+            shell,
+            profiles,
+            backed_up: vec![],
+        };
+
+        if !installed || this.profiles.is_empty() {
+            tracing::debug!("No `{shell}` profiles to back up");
+            return Ok(StatefulAction::skipped(this));
+        }
+
+        Ok(StatefulAction::uncompleted(this))
+    }
+}
+
+/// The profile files of `shell` that [`ConfigureShellProfile`](crate::action::common::ConfigureShellProfile) may modify
+pub(super) fn profiles_for(shell: Shell, locations: &ShellProfileLocations) -> Vec<PathBuf> {
+    match shell {
+        Shell::Bash => locations.bash.clone(),
+        Shell::Zsh => locations.zsh.clone(),
+        Shell::Fish => locations
+            .fish
+            .confd_prefixes
+            .iter()
+            .map(|prefix| prefix.join(&locations.fish.confd_suffix))
+            .chain(
+                locations
+                    .fish
+                    .vendor_confd_prefixes
+                    .iter()
+                    .map(|prefix| prefix.join(&locations.fish.vendor_confd_suffix)),
+            )
+            .collect(),
+        // None of these have a profile of their own which is modified
+        Shell::Sh | Shell::Nu | Shell::Pwsh => vec![],
+    }
+}
+
+fn backup_path(profile: &Path) -> PathBuf {
+    let mut backup = profile.as_os_str().to_owned();
+    backup.push(".");
+    backup.push(BACKUP_SUFFIX);
+    PathBuf::from(backup)
+}
+
+#[async_trait::async_trait]
+#[typetag::serde(name = "backup_shell_profile")]
+impl Action for BackupShellProfile {
+    fn action_tag() -> ActionTag {
+        ActionTag("backup_shell_profile")
+    }
+    fn paths_written(&self) -> Vec<PathBuf> {
+        self.profiles
+            .iter()
+            .map(|profile| backup_path(profile))
+            .collect()
+    }
+    fn paths_read(&self) -> Vec<PathBuf> {
+        self.profiles.clone()
+    }
+    fn tracing_synopsis(&self) -> String {
+        format!("Back up the `{}` profiles", self.shell)
+    }
+
+    fn tracing_span(&self) -> Span {
+        span!(
+            tracing::Level::DEBUG,
+            "backup_shell_profile",
+            shell = tracing::field::display(self.shell),
+        )
+    }
+
+    fn execute_description(&self) -> Vec<ActionDescription> {
+        vec![ActionDescription::new(
+            self.tracing_synopsis(),
+            self.profiles
+                .iter()
+                .map(|profile| {
+                    format!(
+                        "Copy `{}` to `{}`",
+                        profile.display(),
+                        backup_path(profile).display()
+                    )
+                })
+                .collect(),
+        )]
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn execute(&mut self) -> Result<(), ActionError> {
+        let Self {
+            shell: _,
+            profiles,
+            backed_up,
+        } = self;
+
+        for profile in profiles.iter() {
+            // The user may have removed it since planning
+            if !profile.exists()
+                || backed_up
+                    .iter()
+                    .any(|backed_up| backed_up.profile == *profile)
+            {
+                continue;
+            }
+            let buf = read(profile)
+                .await
+                .map_err(|e| ActionErrorKind::Read(profile.clone(), e))
+                .map_err(Self::error)?;
+            let backup = backup_path(profile);
+            copy(profile, &backup)
+                .await
+                .map_err(|e| ActionErrorKind::Copy(profile.clone(), backup.clone(), e))
+                .map_err(Self::error)?;
+            backed_up.push(BackedUpProfile {
+                profile: profile.clone(),
+                sha256: sha256_hex(&buf),
+            });
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn dry_run(&self) {
+        for profile in &self.profiles {
+            tracing::info!(
+                "Would copy `{}` to `{}`",
+                profile.display(),
+                backup_path(profile).display()
+            );
+        }
+    }
+
+    fn revert_description(&self) -> Vec<ActionDescription> {
+        vec![ActionDescription::new(
+            format!("Restore the `{}` profiles", self.shell),
+            self.backed_up
+                .iter()
+                .map(|BackedUpProfile { profile, .. }| {
+                    format!(
+                        "Restore `{}` from `{}`, unless it was edited since",
+                        profile.display(),
+                        backup_path(profile).display()
+                    )
+                })
+                .collect(),
+        )]
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn revert(&mut self) -> Result<(), ActionError> {
+        let mut errors = vec![];
+
+        for backed_up in std::mem::take(&mut self.backed_up) {
+            let profile = &backed_up.profile;
+            let backup = backup_path(profile);
+            // The user already restored it
+            if !backup.exists() {
+                continue;
+            }
+            let current = match read(profile).await {
+                Ok(buf) => Some(sha256_hex(&buf)),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                Err(e) => {
+                    errors.push(Self::error(ActionErrorKind::Read(profile.clone(), e)));
+                    self.backed_up.push(backed_up);
+                    continue;
+                },
+            };
+            if current.as_ref() != Some(&backed_up.sha256) {
+                tracing::warn!(
+                    "`{}` was edited since it was backed up, keeping it, the backup is in `{}`",
+                    profile.display(),
+                    backup.display()
+                );
+                continue;
+            }
+            let restored = match copy(&backup, profile).await {
+                Ok(_) => remove_file(&backup)
+                    .await
+                    .map_err(|e| ActionErrorKind::Remove(backup.clone(), e)),
+                Err(e) => Err(ActionErrorKind::Copy(backup.clone(), profile.clone(), e)),
+            };
+            if let Err(err) = restored {
+                errors.push(Self::error(err));
+                self.backed_up.push(backed_up);
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else if errors.len() == 1 {
+            Err(errors
+                .into_iter()
+                .next()
+                .expect("Expected 1 len Vec to have at least 1 item"))
+        } else {
+            Err(Self::error(ActionErrorKind::MultipleChildren(errors)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tokio::fs::{read_to_string, write};
+
+    #[tokio::test]
+    async fn backs_up_and_restores_profiles() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let bashrc = temp_dir.path().join("bashrc");
+        let missing = temp_dir.path().join("bash.bashrc");
+        write(&bashrc, "# Original\n").await?;
+
+        let locations = ShellProfileLocations {
+            bash: vec![bashrc.clone(), missing.clone()],
+            ..Default::default()
+        };
+        let mut action =
+            BackupShellProfile::plan_if_installed(Shell::Bash, true, &locations).await?;
+
+        action.try_execute().await?;
+        assert_eq!(
+            action
+                .inner()
+                .backed_up
+                .iter()
+                .map(|backed_up| &backed_up.profile)
+                .collect::<Vec<_>>(),
+            vec![&bashrc]
+        );
+        assert!(backup_path(&bashrc).exists(), "Backup should exist");
+        assert!(
+            !backup_path(&missing).exists(),
+            "Missing profile is skipped"
+        );
+
+        action.try_revert().await?;
+
+        assert_eq!(read_to_string(&bashrc).await?, "# Original\n");
+        assert!(!backup_path(&bashrc).exists(), "Backup should be removed");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn keeps_profiles_edited_since_the_backup() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let bashrc = temp_dir.path().join("bashrc");
+        write(&bashrc, "# Original\n").await?;
+
+        let locations = ShellProfileLocations {
+            bash: vec![bashrc.clone()],
+            ..Default::default()
+        };
+        let mut action =
+            BackupShellProfile::plan_if_installed(Shell::Bash, true, &locations).await?;
+
+        action.try_execute().await?;
+        write(&bashrc, "# Original\n# Edited\n").await?;
+        action.try_revert().await?;
+
+        assert_eq!(read_to_string(&bashrc).await?, "# Original\n# Edited\n");
+        assert_eq!(read_to_string(backup_path(&bashrc)).await?, "# Original\n");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn keeps_existing_backups() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let bashrc = temp_dir.path().join("bashrc");
+        write(&bashrc, "# Installed\n").await?;
+        write(backup_path(&bashrc), "# Original\n").await?;
+
+        let locations = ShellProfileLocations {
+            bash: vec![bashrc.clone()],
+            ..Default::default()
+        };
+        let mut action =
+            BackupShellProfile::plan_if_installed(Shell::Bash, true, &locations).await?;
+        assert_eq!(action.state, crate::action::ActionState::Skipped);
+
+        action.try_execute().await?;
+        action.try_revert().await?;
+        assert_eq!(read_to_string(backup_path(&bashrc)).await?, "# Original\n");
+        assert_eq!(read_to_string(&bashrc).await?, "# Installed\n");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,199 @@
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use nix::unistd::User;
+use tracing::{span, Span};
+
+use crate::action::{
+    Action, ActionDescription, ActionError, ActionErrorKind, ActionTag, StatefulAction,
+};
+
+/**
+Check the Nix store exists with the ownership and permissions expected for the install, so that actions
+using it (such as [`UpdateChannels`](crate::action::common::UpdateChannels)) do not fail cryptically
+
+With no `user` the store is expected to be owned by root and not world-writable, as in a multi-user install.
+With a `user` it is expected to be owned and writable by them, as in a single-user install. Does nothing on revert.
+ */
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone)]
+pub struct CheckNixStore {
+    path: PathBuf,
+    user: Option<String>,
+}
+
+impl CheckNixStore {
+    #[tracing::instrument(level = "debug", skip_all)]
+    pub async fn plan(
+        path: impl AsRef<Path>,
+        user: impl Into<Option<String>>,
+    ) -> Result<StatefulAction<Self>, ActionError> {
+        let user = user.into();
+        if let Some(user) = &user {
+            User::from_name(user)
+                .map_err(|e| ActionErrorKind::GettingUserId(user.clone(), e))
+                .map_err(Self::error)?
+                .ok_or_else(|| ActionErrorKind::NoUser(user.clone()))
+                .map_err(Self::error)?;
+        }
+        // The store usually does not exist until earlier actions create it, so it is checked on execute
+        Ok(StatefulAction::uncompleted(Self {
+            path: path.as_ref().to_path_buf(),
+            user,
+        }))
+    }
+}
+
+#[async_trait::async_trait]
+#[typetag::serde(name = "check_nix_store")]
+impl Action for CheckNixStore {
+    fn action_tag() -> ActionTag {
+        ActionTag("check_nix_store")
+    }
+    fn paths_read(&self) -> Vec<PathBuf> {
+        vec![self.path.clone()]
+    }
+    fn tracing_synopsis(&self) -> String {
+        format!("Check the Nix store at `{}` is usable", self.path.display())
+    }
+
+    fn tracing_span(&self) -> Span {
+        span!(
+            tracing::Level::DEBUG,
+            "check_nix_store",
+            path = tracing::field::display(self.path.display()),
+            user = self.user,
+        )
+    }
+
+    fn execute_description(&self) -> Vec<ActionDescription> {
+        let expected = match &self.user {
+            Some(user) => format!("Expects it to be owned and writable by `{user}`"),
+            None => "Expects it to be owned by `root` and not world-writable".to_string(),
+        };
+        vec![ActionDescription::new(
+            self.tracing_synopsis(),
+            vec![expected],
+        )]
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn execute(&mut self) -> Result<(), ActionError> {
+        let Self { path, user } = self;
+
+        if !path.is_dir() {
+            return Err(Self::error(CheckNixStoreError::Missing {
+                path: path.clone(),
+            }));
+        }
+        let metadata = path
+            .metadata()
+            .map_err(|e| Self::error(ActionErrorKind::GettingMetadata(path.clone(), e)))?;
+        // Listing it verifies it is readable
+        let _ = tokio::fs::read_dir(&path)
+            .await
+            .map_err(|e| Self::error(ActionErrorKind::ReadDir(path.clone(), e)))?;
+
+        let mode = metadata.permissions().mode() & 0o7777;
+        let problem = match user {
+            Some(user) => {
+                let uid = User::from_name(user)
+                    .map_err(|e| ActionErrorKind::GettingUserId(user.clone(), e))
+                    .map_err(Self::error)?
+                    .ok_or_else(|| ActionErrorKind::NoUser(user.clone()))
+                    .map_err(Self::error)?
+                    .uid;
+                if metadata.uid() != uid.as_raw() {
+                    Some(format!("not owned by `{user}`"))
+                } else if mode & 0o200 == 0 {
+                    Some(format!("not writable by `{user}`"))
+                } else {
+                    None
+                }
+            },
+            None => {
+                if metadata.uid() != 0 {
+                    Some("not owned by `root`".to_string())
+                } else if mode & 0o002 != 0 {
+                    Some("world-writable".to_string())
+                } else {
+                    None
+                }
+            },
+        };
+
+        match problem {
+            Some(problem) => Err(Self::error(CheckNixStoreError::WrongPermissions {
+                path: path.clone(),
+                problem,
+                mode,
+                uid: metadata.uid(),
+            })),
+            None => Ok(()),
+        }
+    }
+
+    fn revert_description(&self) -> Vec<ActionDescription> {
+        vec![/* Deliberately empty -- this is a noop */]
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn revert(&mut self) -> Result<(), ActionError> {
+        Ok(())
+    }
+}
+
+#[non_exhaustive]
+#[derive(Debug, thiserror::Error, strum::IntoStaticStr)]
+pub enum CheckNixStoreError {
+    #[error("The Nix store `{}` does not exist, the install may be incomplete", .path.display())]
+    Missing { path: PathBuf },
+    #[error("The Nix store `{}` is {problem} (it has mode `{mode:#o}` and is owned by uid {uid})", .path.display())]
+    WrongPermissions {
+        path: PathBuf,
+        problem: String,
+        mode: u32,
+        uid: u32,
+    },
+}
+
+impl From<CheckNixStoreError> for ActionErrorKind {
+    fn from(val: CheckNixStoreError) -> Self {
+        ActionErrorKind::Custom(Box::new(val))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn check_nix_store_error(err: &ActionError) -> Option<&CheckNixStoreError> {
+        let ActionErrorKind::Custom(err) = err.kind() else {
+            return None;
+        };
+        err.downcast_ref::<CheckNixStoreError>()
+    }
+
+    #[tokio::test]
+    async fn distinguishes_missing_from_wrong_permissions() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let store = temp_dir.path().join("store");
+
+        let mut action = CheckNixStore::plan(&store, None).await?;
+        let err = action.try_execute().await.unwrap_err();
+        assert!(matches!(
+            check_nix_store_error(&err),
+            Some(CheckNixStoreError::Missing { path }) if *path == store
+        ));
+
+        tokio::fs::create_dir(&store).await?;
+        tokio::fs::set_permissions(&store, PermissionsExt::from_mode(0o777)).await?;
+        let mut action = CheckNixStore::plan(&store, None).await?;
+        let err = action.try_execute().await.unwrap_err();
+        assert!(matches!(
+            check_nix_store_error(&err),
+            Some(CheckNixStoreError::WrongPermissions { path, mode: 0o777, .. }) if *path == store
+        ));
+
+        Ok(())
+    }
+}
@@ -7,8 +7,8 @@ use tokio::fs::{create_dir, remove_dir_all, remove_file};
 use tokio::process::Command;
 use tracing::{span, Span};
 
+use crate::action::{stateful::new_action_id, ActionError, StatefulAction};
 use crate::action::{Action, ActionDescription, ActionErrorKind, ActionState};
-use crate::action::{ActionError, StatefulAction};
 use crate::execute_command;
 
 /** Create a directory at the given location, optionally with an owning user, group, and mode.
@@ -110,6 +110,8 @@ impl CreateDirectory {
                 force_prune_on_revert,
             },
             state: action_state,
+            id: new_action_id(),
+            elapsed: None,
         })
     }
 }
@@ -120,10 +122,17 @@ impl Action for CreateDirectory {
     fn action_tag() -> crate::action::ActionTag {
         crate::action::ActionTag("create_directory")
     }
+    fn paths_written(&self) -> Vec<PathBuf> {
+        vec![self.path.clone()]
+    }
     fn tracing_synopsis(&self) -> String {
         format!("Create directory `{}`", self.path.display())
     }
 
+    fn estimated_duration(&self) -> Option<std::time::Duration> {
+        Some(std::time::Duration::from_millis(10))
+    }
+
     fn tracing_span(&self) -> Span {
         span!(
             tracing::Level::DEBUG,
@@ -200,6 +209,40 @@ impl Action for CreateDirectory {
         Ok(())
     }
 
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn verify(&self) -> Result<(), ActionError> {
+        if self.is_mountpoint {
+            return Ok(());
+        }
+        let metadata = tokio::fs::metadata(&self.path)
+            .await
+            .map_err(|e| ActionErrorKind::GettingMetadata(self.path.clone(), e))
+            .map_err(Self::error)?;
+        if !metadata.is_dir() {
+            return Err(Self::error(ActionErrorKind::PathWasNotDirectory(
+                self.path.clone(),
+            )));
+        }
+        if let Some(mode) = self.mode {
+            let discovered_mode = metadata.permissions().mode() & 0o777;
+            if discovered_mode != mode {
+                return Err(Self::error(ActionErrorKind::PathModeMismatch(
+                    self.path.clone(),
+                    discovered_mode,
+                    mode,
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn dry_run(&self) {
+        if !self.is_mountpoint {
+            tracing::info!("Would create directory `{}`", self.path.display());
+        }
+    }
+
     fn revert_description(&self) -> Vec<ActionDescription> {
         let Self {
             path,
@@ -241,6 +284,11 @@ impl Action for CreateDirectory {
             force_prune_on_revert,
         } = self;
 
+        // A parent was already pruned, or the user already deleted it
+        if !path.exists() {
+            return Ok(());
+        }
+
         let contents = path
             .read_dir()
             .map_err(|e| ActionErrorKind::Read(path.clone(), e))
@@ -395,4 +443,21 @@ mod test {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn reverts_already_deleted_directory() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let test_dir = temp_dir.path().join("reverts_already_deleted_directory");
+        let mut action = CreateDirectory::plan(test_dir.clone(), None, None, None, false).await?;
+
+        action.try_execute().await?;
+
+        tokio::fs::remove_dir(&test_dir).await?;
+
+        action.try_revert().await?;
+
+        assert!(!test_dir.exists(), "Folder should still not exist");
+
+        Ok(())
+    }
 }
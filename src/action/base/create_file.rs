@@ -1,9 +1,12 @@
-use nix::unistd::{chown, Group, User};
+use nix::unistd::{chown, Gid, Group, Uid, User};
+use rand::Rng;
 use tracing::{span, Span};
 
 use std::{
+    ops::Range,
     os::{unix::fs::MetadataExt, unix::fs::PermissionsExt},
     path::{Path, PathBuf},
+    sync::Arc,
 };
 use tokio::{
     fs::{remove_file, File, OpenOptions},
@@ -11,14 +14,26 @@ use tokio::{
 };
 
 use crate::action::{
-    Action, ActionDescription, ActionError, ActionErrorKind, ActionTag, StatefulAction,
+    Action, ActionDescription, ActionError, ActionErrorKind, ActionStateReport, ActionTag,
+    StatefulAction,
 };
 
 /** Create a file at the given location with the provided `buf`,
 optionally with an owning user, group, and mode.
 
-If `force` is set, the file will always be overwritten (and deleted)
-regardless of its presence prior to install.
+Planned with [`CreateFile::plan_with_options`], `buf` can be verified against an
+expected hash before anything is written, and missing parent directories can
+be created, see [`CreateFileOptions`].
+
+If `force` is set, the file will always be overwritten regardless of its
+presence prior to install. A file which was overwritten is restored,
+with its original mode and ownership, on revert instead of being deleted.
+Interactive callers can be asked before that happens with
+[`StatefulAction::with_overwrite_confirmation`].
+
+When planned with [`CreateFile::plan_block`], only the block between a pair of
+[`BlockMarkers`] is written, leaving the rest of a file other tools may also
+edit untouched. On revert only that block is removed.
  */
 #[derive(Debug, serde::Deserialize, serde::Serialize, Clone)]
 pub struct CreateFile {
@@ -27,7 +42,162 @@ pub struct CreateFile {
     group: Option<String>,
     mode: Option<u32>,
     buf: String,
+    #[serde(default)]
+    expected_hash: Option<String>,
     force: bool,
+    #[serde(default)]
+    create_parents: bool,
+    /// The file `force` overwrote, restored on revert
+    #[serde(default)]
+    prior: Option<PriorFile>,
+    /// The parent directories `create_parents` created, outermost first
+    #[serde(default)]
+    created_parents: Vec<PathBuf>,
+    /// Only manage the block between these markers, see [`CreateFile::plan_block`]
+    #[serde(default)]
+    block: Option<BlockMarkers>,
+    #[serde(skip)]
+    confirm_overwrite: Option<OverwriteConfirmation>,
+}
+
+/// How executing a [`CreateFile`] would change the file, see [`CreateFile::pending_change`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileChange {
+    /// The file does not exist yet
+    Missing,
+    /// Something other than a file is at the path
+    NotAFile,
+    /// The file has a different mode than planned
+    Mode { found: u32, planned: u32 },
+    /// The file has different content than planned
+    Content,
+}
+
+/// Asked whether `force` may overwrite an existing file, see [`StatefulAction::with_overwrite_confirmation`]
+#[derive(Clone)]
+pub struct OverwriteConfirmation(Arc<dyn Fn(&Path) -> bool + Send + Sync>);
+
+impl std::fmt::Debug for OverwriteConfirmation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("OverwriteConfirmation").finish()
+    }
+}
+
+impl StatefulAction<CreateFile> {
+    /// Call `confirm` with the path before an existing file is overwritten, declining with [`CreateFileError::OverwriteDeclined`] if it returns `false`
+    ///
+    /// Only consulted when the action was planned with `force` and the file exists at execute time.
+    pub fn with_overwrite_confirmation(
+        mut self,
+        confirm: impl Fn(&Path) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.action.confirm_overwrite = Some(OverwriteConfirmation(Arc::new(confirm)));
+        self
+    }
+}
+
+/// The lines delimiting the block [`CreateFile::plan_block`] manages inside a file
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, PartialEq, Eq)]
+pub struct BlockMarkers {
+    pub begin: String,
+    pub end: String,
+    /// If the file did not exist before the block was written
+    #[serde(default)]
+    created_file: bool,
+}
+
+impl BlockMarkers {
+    pub fn new(begin: impl Into<String>, end: impl Into<String>) -> Self {
+        Self {
+            begin: begin.into(),
+            end: end.into(),
+            created_file: false,
+        }
+    }
+
+    /// Find the byte range of the block, including both marker lines, in `content`
+    fn find(&self, path: &Path, content: &str) -> Result<Option<Range<usize>>, ActionErrorKind> {
+        let malformed = |reason| ActionErrorKind::MalformedMarkers {
+            path: path.to_path_buf(),
+            begin: self.begin.clone(),
+            end: self.end.clone(),
+            reason,
+        };
+
+        let mut found = None;
+        let mut start = None;
+        let mut offset = 0;
+        for line in content.split_inclusive('\n') {
+            let trimmed = line.trim_end();
+            if trimmed == self.begin {
+                if start.is_some() {
+                    return Err(malformed("nested begin marker"));
+                }
+                if found.is_some() {
+                    return Err(malformed("more than one block"));
+                }
+                start = Some(offset);
+            } else if trimmed == self.end {
+                match start.take() {
+                    Some(start) => found = Some(start..offset + line.len()),
+                    None => return Err(malformed("end marker without a begin marker")),
+                }
+            }
+            offset += line.len();
+        }
+        if start.is_some() {
+            return Err(malformed("begin marker without an end marker"));
+        }
+        Ok(found)
+    }
+
+    /// Replace the block in `content` with one holding `buf`, or append it if there is none
+    fn apply(&self, path: &Path, content: String, buf: &str) -> Result<String, ActionErrorKind> {
+        let block = self.render(buf);
+        Ok(match self.find(path, &content)? {
+            Some(range) => {
+                let mut content = content;
+                content.replace_range(range, &block);
+                content
+            },
+            None if content.is_empty() || content.ends_with('\n') => content + &block,
+            None => format!("{content}\n{block}"),
+        })
+    }
+
+    /// The block, including both marker lines, holding `buf`
+    fn render(&self, buf: &str) -> String {
+        let newline = if buf.is_empty() || buf.ends_with('\n') {
+            ""
+        } else {
+            "\n"
+        };
+        format!("{}\n{buf}{newline}{}\n", self.begin, self.end)
+    }
+}
+
+impl Default for BlockMarkers {
+    fn default() -> Self {
+        Self::new("# >>> nix-installer", "# <<< nix-installer")
+    }
+}
+
+/// A snapshot of a file which existed before [`CreateFile`] overwrote it (or [`RemoveFile`](super::RemoveFile) removed it)
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone)]
+pub(super) struct PriorFile {
+    pub(super) buf: String,
+    pub(super) mode: u32,
+    pub(super) uid: u32,
+    pub(super) gid: u32,
+}
+
+/// Less common settings of a [`CreateFile`], see [`CreateFile::plan_with_options`]
+#[derive(Debug, Default, Clone)]
+pub struct CreateFileOptions {
+    /// Verify `buf` against this digest, as `sha256:<hex>` or bare hex, before anything is written
+    pub expected_hash: Option<String>,
+    /// Create any missing parent directories, removing them again on revert if they are empty
+    pub create_parents: bool,
 }
 
 impl CreateFile {
@@ -39,18 +209,54 @@ impl CreateFile {
         mode: impl Into<Option<u32>>,
         buf: String,
         force: bool,
+    ) -> Result<StatefulAction<Self>, ActionError> {
+        Self::plan_with_options(
+            path,
+            user,
+            group,
+            mode,
+            buf,
+            force,
+            CreateFileOptions::default(),
+        )
+        .await
+    }
+
+    /// Like [`CreateFile::plan`], with the [`CreateFileOptions`]
+    #[tracing::instrument(level = "debug", skip_all)]
+    pub async fn plan_with_options(
+        path: impl AsRef<Path>,
+        user: impl Into<Option<String>>,
+        group: impl Into<Option<String>>,
+        mode: impl Into<Option<u32>>,
+        buf: String,
+        force: bool,
+        options: CreateFileOptions,
     ) -> Result<StatefulAction<Self>, ActionError> {
         let path = path.as_ref().to_path_buf();
         let mode = mode.into();
         let user = user.into();
         let group = group.into();
-        let this = Self {
+        let CreateFileOptions {
+            expected_hash,
+            create_parents,
+        } = options;
+        if let Some(expected_hash) = &expected_hash {
+            verify_sha256(&path, buf.as_bytes(), expected_hash).map_err(Self::error)?;
+        }
+        let mut this = Self {
             path,
             user,
             group,
             mode,
             buf,
+            expected_hash,
             force,
+            create_parents,
+            prior: None,
+            created_parents: vec![],
+            block: None,
+            confirm_overwrite: None,
         };
 
         if this.path.exists() {
@@ -70,75 +276,312 @@ impl CreateFile {
                 return Err(Self::error(ActionErrorKind::PathWasNotFile(this.path)));
             }
 
-            if let Some(mode) = mode {
-                // Does the file have the right permissions?
-                let discovered_mode = metadata.permissions().mode();
-                // We only care about user-group-other permissions
-                let discovered_mode = discovered_mode & 0o777;
-
-                if discovered_mode != mode {
-                    return Err(Self::error(ActionErrorKind::PathModeMismatch(
-                        this.path.clone(),
-                        discovered_mode,
-                        mode,
-                    )));
-                }
+            let mut discovered_buf = String::new();
+            file.read_to_string(&mut discovered_buf)
+                .await
+                .map_err(|e| ActionErrorKind::Read(this.path.clone(), e))
+                .map_err(Self::error)?;
+
+            match this.find_mismatch(&metadata, &discovered_buf)? {
+                None => {
+                    tracing::debug!("Creating file `{}` already complete", this.path.display());
+                    return Ok(StatefulAction::completed(this));
+                },
+                Some(_) if this.force => {
+                    // Keep the file we are about to overwrite so revert can put it back
+                    tracing::debug!(
+                        "Will overwrite existing file `{}`, keeping a copy for revert",
+                        this.path.display()
+                    );
+                    this.prior = Some(PriorFile {
+                        buf: discovered_buf,
+                        mode: metadata.permissions().mode() & 0o7777,
+                        uid: metadata.uid(),
+                        gid: metadata.gid(),
+                    });
+                },
+                Some(mismatch) => return Err(Self::error(mismatch)),
             }
+        }
 
-            // Does it have the right user/group?
-            if let Some(user) = &this.user {
-                // If the file exists, the user must also exist to be correct.
-                let expected_uid = User::from_name(user.as_str())
-                    .map_err(|e| ActionErrorKind::GettingUserId(user.clone(), e))
-                    .map_err(Self::error)?
-                    .ok_or_else(|| ActionErrorKind::NoUser(user.clone()))
-                    .map_err(Self::error)?
-                    .uid;
-                let found_uid = metadata.uid();
-                if found_uid != expected_uid.as_raw() {
-                    return Err(Self::error(ActionErrorKind::PathUserMismatch(
-                        this.path.clone(),
-                        found_uid,
-                        expected_uid.as_raw(),
-                    )));
-                }
+        Ok(StatefulAction::uncompleted(this))
+    }
+
+    /// Like [`CreateFile::plan`], but with `buf` rendered from `template` by replacing each `{{key}}` with its value in `substitutions`
+    ///
+    /// Any placeholder without a substitution is refused with [`CreateFileError::UnresolvedPlaceholder`]. Values are inserted as is, so they may contain braces.
+    #[tracing::instrument(level = "debug", skip_all)]
+    pub async fn plan_template(
+        path: impl AsRef<Path>,
+        user: impl Into<Option<String>>,
+        group: impl Into<Option<String>>,
+        mode: impl Into<Option<u32>>,
+        template: &str,
+        substitutions: &[(&str, &str)],
+        options: CreateFileOptions,
+    ) -> Result<StatefulAction<Self>, ActionError> {
+        let buf = render_template(template, substitutions).map_err(Self::error)?;
+        Self::plan_with_options(path, user, group, mode, buf, false, options).await
+    }
+
+    /// Like [`CreateFile::plan`], but only manage the block between `markers`, creating the file if it is missing
+    ///
+    /// Re-running replaces the existing block in place, and reverting removes only the block.
+    /// The markers must appear at most once, in order, and not be nested.
+    #[tracing::instrument(level = "debug", skip_all)]
+    pub async fn plan_block(
+        path: impl AsRef<Path>,
+        user: impl Into<Option<String>>,
+        group: impl Into<Option<String>>,
+        mode: impl Into<Option<u32>>,
+        buf: String,
+        markers: BlockMarkers,
+        create_parents: bool,
+    ) -> Result<StatefulAction<Self>, ActionError> {
+        let this = Self {
+            path: path.as_ref().to_path_buf(),
+            user: user.into(),
+            group: group.into(),
+            mode: mode.into(),
+            buf,
+            expected_hash: None,
+            force: false,
+            create_parents,
+            prior: None,
+            created_parents: vec![],
+            block: Some(markers),
+            confirm_overwrite: None,
+        };
+
+        if this.path.exists() {
+            let metadata = tokio::fs::metadata(&this.path)
+                .await
+                .map_err(|e| ActionErrorKind::GettingMetadata(this.path.clone(), e))
+                .map_err(Self::error)?;
+            if !metadata.is_file() {
+                return Err(Self::error(ActionErrorKind::PathWasNotFile(this.path)));
             }
-            if let Some(group) = &this.group {
-                // If the file exists, the group must also exist to be correct.
-                let expected_gid = Group::from_name(group.as_str())
-                    .map_err(|e| ActionErrorKind::GettingGroupId(group.clone(), e))
-                    .map_err(Self::error)?
-                    .ok_or_else(|| ActionErrorKind::NoUser(group.clone()))
-                    .map_err(Self::error)?
-                    .gid;
-                let found_gid = metadata.gid();
-                if found_gid != expected_gid.as_raw() {
-                    return Err(Self::error(ActionErrorKind::PathGroupMismatch(
-                        this.path.clone(),
-                        found_gid,
-                        expected_gid.as_raw(),
-                    )));
-                }
+            if this.block_is_complete().await? {
+                tracing::debug!(
+                    "Creating block in file `{}` already complete",
+                    this.path.display()
+                );
+                return Ok(StatefulAction::completed(this));
             }
+        }
 
-            // Does it have the right content?
-            let mut discovered_buf = String::new();
-            file.read_to_string(&mut discovered_buf)
+        Ok(StatefulAction::uncompleted(this))
+    }
+
+    /// If executing would change the filesystem, see [`CreateFile::pending_change`]
+    pub fn would_change(&self) -> bool {
+        self.pending_change().is_some()
+    }
+
+    /// How the file on disk differs from the planned one, without changing anything
+    ///
+    /// Only the planned block is compared for files planned with [`CreateFile::plan_block`].
+    /// Files which cannot be read are assumed to differ in content.
+    pub fn pending_change(&self) -> Option<FileChange> {
+        let metadata = match std::fs::metadata(&self.path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Some(FileChange::Missing),
+            Err(_) => return Some(FileChange::Content),
+        };
+        if !metadata.is_file() {
+            return Some(FileChange::NotAFile);
+        }
+        if let Some(planned) = self.mode {
+            let found = metadata.permissions().mode() & 0o777;
+            if found != planned {
+                return Some(FileChange::Mode { found, planned });
+            }
+        }
+        let Ok(content) = std::fs::read_to_string(&self.path) else {
+            return Some(FileChange::Content);
+        };
+        let unchanged = match &self.block {
+            Some(markers) => markers
+                .find(&self.path, &content)
+                .ok()
+                .flatten()
+                .is_some_and(|range| content[range] == markers.render(&self.buf)),
+            None => content == self.buf,
+        };
+        if unchanged {
+            None
+        } else {
+            Some(FileChange::Content)
+        }
+    }
+
+    /// If the file already holds exactly the planned block
+    async fn block_is_complete(&self) -> Result<bool, ActionError> {
+        let markers = self.block.as_ref().expect("Only called in block mode");
+        let content = tokio::fs::read_to_string(&self.path)
+            .await
+            .map_err(|e| ActionErrorKind::Read(self.path.clone(), e))
+            .map_err(Self::error)?;
+        let found = markers.find(&self.path, &content).map_err(Self::error)?;
+        Ok(found.is_some_and(|range| content[range] == markers.render(&self.buf)))
+    }
+
+    async fn execute_block(
+        &mut self,
+        uid: Option<Uid>,
+        gid: Option<Gid>,
+    ) -> Result<(), ActionError> {
+        let markers = self.block.as_mut().expect("Only called in block mode");
+        let (content, uid, gid, mode) = match tokio::fs::metadata(&self.path).await {
+            Ok(metadata) => {
+                let content = tokio::fs::read_to_string(&self.path)
+                    .await
+                    .map_err(|e| ActionErrorKind::Read(self.path.clone(), e))
+                    .map_err(Self::error)?;
+                // Keep the existing ownership and mode unless told otherwise
+                (
+                    content,
+                    uid.or(Some(Uid::from_raw(metadata.uid()))),
+                    gid.or(Some(Gid::from_raw(metadata.gid()))),
+                    self.mode.or(Some(metadata.permissions().mode() & 0o7777)),
+                )
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                markers.created_file = true;
+                (String::new(), uid, gid, self.mode)
+            },
+            Err(e) => {
+                return Err(Self::error(ActionErrorKind::GettingMetadata(
+                    self.path.clone(),
+                    e,
+                )))
+            },
+        };
+
+        let content = markers
+            .apply(&self.path, content, &self.buf)
+            .map_err(Self::error)?;
+
+        let temp_file_path = temp_file_path(&self.path);
+        if let Err(err) =
+            write_temp_file(&temp_file_path, &self.path, &content, uid, gid, mode).await
+        {
+            remove_temp_file(&temp_file_path).await;
+            return Err(Self::error(err));
+        }
+        Ok(())
+    }
+
+    async fn revert_block(&mut self) -> Result<(), ActionError> {
+        let markers = self.block.as_ref().expect("Only called in block mode");
+        let metadata = match tokio::fs::metadata(&self.path).await {
+            Ok(metadata) => metadata,
+            // The user already deleted it
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => {
+                return Err(Self::error(ActionErrorKind::GettingMetadata(
+                    self.path.clone(),
+                    e,
+                )))
+            },
+        };
+        let mut content = tokio::fs::read_to_string(&self.path)
+            .await
+            .map_err(|e| ActionErrorKind::Read(self.path.clone(), e))
+            .map_err(Self::error)?;
+        let Some(range) = markers.find(&self.path, &content).map_err(Self::error)? else {
+            // The user already removed the block
+            return Ok(());
+        };
+        content.replace_range(range, "");
+
+        if markers.created_file && content.trim().is_empty() {
+            remove_file(&self.path)
                 .await
-                .map_err(|e| ActionErrorKind::Read(this.path.clone(), e))
+                .map_err(|e| ActionErrorKind::Remove(self.path.clone(), e))
                 .map_err(Self::error)?;
+            return Ok(());
+        }
 
-            if discovered_buf != this.buf {
-                return Err(Self::error(ActionErrorKind::DifferentContent(
-                    this.path.clone(),
+        let temp_file_path = temp_file_path(&self.path);
+        if let Err(err) = write_temp_file(
+            &temp_file_path,
+            &self.path,
+            &content,
+            Some(Uid::from_raw(metadata.uid())),
+            Some(Gid::from_raw(metadata.gid())),
+            Some(metadata.permissions().mode() & 0o7777),
+        )
+        .await
+        {
+            remove_temp_file(&temp_file_path).await;
+            return Err(Self::error(err));
+        }
+        Ok(())
+    }
+
+    /// Find how an existing file differs from the planned one, if it does
+    fn find_mismatch(
+        &self,
+        metadata: &std::fs::Metadata,
+        discovered_buf: &str,
+    ) -> Result<Option<ActionErrorKind>, ActionError> {
+        if let Some(mode) = self.mode {
+            // Does the file have the right permissions?
+            let discovered_mode = metadata.permissions().mode();
+            // We only care about user-group-other permissions
+            let discovered_mode = discovered_mode & 0o777;
+
+            if discovered_mode != mode {
+                return Ok(Some(ActionErrorKind::PathModeMismatch(
+                    self.path.clone(),
+                    discovered_mode,
+                    mode,
                 )));
             }
+        }
 
-            tracing::debug!("Creating file `{}` already complete", this.path.display());
-            return Ok(StatefulAction::completed(this));
+        // Does it have the right user/group?
+        if let Some(user) = &self.user {
+            // If the file exists, the user must also exist to be correct.
+            let expected_uid = User::from_name(user.as_str())
+                .map_err(|e| ActionErrorKind::GettingUserId(user.clone(), e))
+                .map_err(Self::error)?
+                .ok_or_else(|| ActionErrorKind::NoUser(user.clone()))
+                .map_err(Self::error)?
+                .uid;
+            let found_uid = metadata.uid();
+            if found_uid != expected_uid.as_raw() {
+                return Ok(Some(ActionErrorKind::PathUserMismatch(
+                    self.path.clone(),
+                    found_uid,
+                    expected_uid.as_raw(),
+                )));
+            }
+        }
+        if let Some(group) = &self.group {
+            // If the file exists, the group must also exist to be correct.
+            let expected_gid = Group::from_name(group.as_str())
+                .map_err(|e| ActionErrorKind::GettingGroupId(group.clone(), e))
+                .map_err(Self::error)?
+                .ok_or_else(|| ActionErrorKind::NoUser(group.clone()))
+                .map_err(Self::error)?
+                .gid;
+            let found_gid = metadata.gid();
+            if found_gid != expected_gid.as_raw() {
+                return Ok(Some(ActionErrorKind::PathGroupMismatch(
+                    self.path.clone(),
+                    found_gid,
+                    expected_gid.as_raw(),
+                )));
+            }
         }
 
-        Ok(StatefulAction::uncompleted(this))
+        // Does it have the right content?
+        if discovered_buf != self.buf {
+            return Ok(Some(ActionErrorKind::DifferentContent(self.path.clone())));
+        }
+        Ok(None)
     }
 }
 
@@ -149,7 +592,18 @@ impl Action for CreateFile {
         ActionTag("create_file")
     }
     fn tracing_synopsis(&self) -> String {
-        format!("Create or overwrite file `{}`", self.path.display())
+        match &self.block {
+            Some(markers) => format!(
+                "Create or update the `{}` block in file `{}`",
+                markers.begin,
+                self.path.display()
+            ),
+            None => format!("Create or overwrite file `{}`", self.path.display()),
+        }
+    }
+
+    fn estimated_duration(&self) -> Option<std::time::Duration> {
+        Some(std::time::Duration::from_millis(10))
     }
 
     fn tracing_span(&self) -> Span {
@@ -172,7 +626,20 @@ impl Action for CreateFile {
     }
 
     fn execute_description(&self) -> Vec<ActionDescription> {
-        vec![ActionDescription::new(self.tracing_synopsis(), vec![])]
+        let mut explanation = vec![];
+        if let Some(mode) = self.mode {
+            explanation.push(format!("Mode: `{mode:#o}`"));
+        }
+        if let Some(user) = &self.user {
+            explanation.push(format!("User: `{user}`"));
+        }
+        if let Some(group) = &self.group {
+            explanation.push(format!("Group: `{group}`"));
+        }
+        if self.create_parents {
+            explanation.push("Create any missing parent directories".to_string());
+        }
+        vec![ActionDescription::new(self.tracing_synopsis(), explanation)]
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
@@ -183,7 +650,13 @@ impl Action for CreateFile {
             group,
             mode,
             buf,
-            force: _,
+            expected_hash,
+            force,
+            create_parents,
+            prior: _,
+            created_parents,
+            block,
+            confirm_overwrite,
         } = self;
 
         if tracing::enabled!(tracing::Level::TRACE) {
@@ -191,23 +664,23 @@ impl Action for CreateFile {
             span.record("buf", &buf);
         }
 
-        let mut options = OpenOptions::new();
-        options.create_new(true).write(true).read(true);
-
-        if let Some(mode) = mode {
-            options.mode(*mode);
+        // The plan may have been deserialized from a receipt, so check again
+        if let Some(expected_hash) = expected_hash {
+            verify_sha256(path, buf.as_bytes(), expected_hash).map_err(Self::error)?;
         }
 
-        let mut file = options
-            .open(&path)
-            .await
-            .map_err(|e| ActionErrorKind::Open(path.to_owned(), e))
-            .map_err(Self::error)?;
-
-        file.write_all(buf.as_bytes())
-            .await
-            .map_err(|e| ActionErrorKind::Write(path.to_owned(), e))
-            .map_err(Self::error)?;
+        if block.is_none() && path.exists() {
+            if !*force {
+                return Err(Self::error(ActionErrorKind::FileExists(path.clone())));
+            }
+            if let Some(OverwriteConfirmation(confirm)) = confirm_overwrite {
+                if !confirm(path) {
+                    return Err(Self::error(CreateFileError::OverwriteDeclined {
+                        path: path.clone(),
+                    }));
+                }
+            }
+        }
 
         let gid = if let Some(group) = group {
             Some(
@@ -233,13 +706,157 @@ impl Action for CreateFile {
         } else {
             None
         };
-        chown(path, uid, gid)
-            .map_err(|e| ActionErrorKind::Chown(path.clone(), e))
+
+        if *create_parents {
+            let parent_dir = path.parent().expect("File must be in a directory");
+            let mut missing = parent_dir
+                .ancestors()
+                .take_while(|ancestor| !ancestor.exists())
+                .map(Path::to_path_buf)
+                .collect::<Vec<_>>();
+            missing.reverse();
+            for dir in missing {
+                tokio::fs::create_dir(&dir)
+                    .await
+                    .map_err(|e| ActionErrorKind::CreateDirectory(dir.clone(), e))
+                    .map_err(Self::error)?;
+                created_parents.push(dir);
+            }
+        }
+
+        if block.is_some() {
+            return self.execute_block(uid, gid).await;
+        }
+
+        let temp_file_path = temp_file_path(path);
+        if let Err(err) = write_temp_file(&temp_file_path, path, buf, uid, gid, *mode).await {
+            remove_temp_file(&temp_file_path).await;
+            return Err(Self::error(err));
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn already_satisfied(&self) -> Result<bool, ActionError> {
+        let metadata = match tokio::fs::metadata(&self.path).await {
+            Ok(metadata) if metadata.is_file() => metadata,
+            // Let execute report anything other than a file
+            _ => return Ok(false),
+        };
+        if self.block.is_some() {
+            return self.block_is_complete().await;
+        }
+        let discovered_buf = tokio::fs::read_to_string(&self.path)
+            .await
+            .map_err(|e| ActionErrorKind::Read(self.path.clone(), e))
             .map_err(Self::error)?;
+        Ok(self.find_mismatch(&metadata, &discovered_buf)?.is_none())
+    }
+
+    async fn state_report(&self) -> Result<ActionStateReport, ActionError> {
+        let mut report = ActionStateReport::new(self.already_satisfied().await?);
+        report.current = tokio::fs::read_to_string(&self.path).await.ok();
+        report.desired = self
+            .planned_contents()
+            .into_iter()
+            .next()
+            .map(|(_, content)| content);
+        if !report.satisfied {
+            let path = self.path.display();
+            report.reasons.push(match self.pending_change() {
+                Some(FileChange::Missing) => format!("`{path}` does not exist"),
+                Some(FileChange::NotAFile) => format!("`{path}` is not a file"),
+                Some(FileChange::Mode { found, planned }) => {
+                    format!("`{path}` has mode `{found:#o}` instead of `{planned:#o}`")
+                },
+                Some(FileChange::Content) | None => {
+                    format!("`{path}` has different content than planned")
+                },
+            });
+        }
+        Ok(report)
+    }
+
+    fn planned_contents(&self) -> Vec<(PathBuf, String)> {
+        let Some(markers) = &self.block else {
+            return vec![(self.path.clone(), self.buf.clone())];
+        };
+        let existing = std::fs::read_to_string(&self.path).unwrap_or_default();
+        match markers.apply(&self.path, existing, &self.buf) {
+            Ok(content) => vec![(self.path.clone(), content)],
+            // Planning would have refused malformed markers, so they were edited since
+            Err(_) => vec![],
+        }
+    }
+
+    fn created_files(&self) -> Vec<PathBuf> {
+        // A block shares the file with whatever else edits it
+        if self.block.is_some() {
+            return vec![];
+        }
+        vec![self.path.clone()]
+    }
+
+    fn paths_written(&self) -> Vec<PathBuf> {
+        vec![self.path.clone()]
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn verify(&self) -> Result<(), ActionError> {
+        if self.block.is_some() {
+            if !self.block_is_complete().await? {
+                return Err(Self::error(ActionErrorKind::DifferentContent(
+                    self.path.clone(),
+                )));
+            }
+            return Ok(());
+        }
 
+        let metadata = tokio::fs::metadata(&self.path)
+            .await
+            .map_err(|e| ActionErrorKind::GettingMetadata(self.path.clone(), e))
+            .map_err(Self::error)?;
+        if !metadata.is_file() {
+            return Err(Self::error(ActionErrorKind::PathWasNotFile(
+                self.path.clone(),
+            )));
+        }
+        if let Some(mode) = self.mode {
+            let discovered_mode = metadata.permissions().mode() & 0o777;
+            if discovered_mode != mode {
+                return Err(Self::error(ActionErrorKind::PathModeMismatch(
+                    self.path.clone(),
+                    discovered_mode,
+                    mode,
+                )));
+            }
+        }
+        let discovered_buf = tokio::fs::read_to_string(&self.path)
+            .await
+            .map_err(|e| ActionErrorKind::Read(self.path.clone(), e))
+            .map_err(Self::error)?;
+        if discovered_buf != self.buf {
+            return Err(Self::error(ActionErrorKind::DifferentContent(
+                self.path.clone(),
+            )));
+        }
         Ok(())
     }
 
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn dry_run(&self) {
+        if !self.would_change() {
+            tracing::info!("Would leave `{}` unchanged", self.path.display());
+            return;
+        }
+        tracing::info!(
+            "Would write {} bytes to `{}`",
+            self.buf.len(),
+            self.path.display()
+        );
+    }
+
     fn revert_description(&self) -> Vec<ActionDescription> {
         let Self {
             path,
@@ -247,13 +864,53 @@ impl Action for CreateFile {
             group: _,
             mode: _,
             buf: _,
+            expected_hash: _,
             force: _,
+            create_parents: _,
+            prior,
+            created_parents,
+            block,
+            confirm_overwrite: _,
         } = &self;
 
-        vec![ActionDescription::new(
-            format!("Delete file `{}`", path.display()),
-            vec![format!("Delete file `{}`", path.display())],
-        )]
+        let mut descriptions = if let Some(markers) = block {
+            vec![ActionDescription::new(
+                format!(
+                    "Remove the `{}` block from file `{}`",
+                    markers.begin,
+                    path.display()
+                ),
+                vec![],
+            )]
+        } else if prior.is_some() {
+            vec![ActionDescription::new(
+                format!("Restore file `{}`", path.display()),
+                vec![format!(
+                    "Restore the {} bytes of file `{}` which existed before install",
+                    prior
+                        .as_ref()
+                        .map(|prior| prior.buf.len())
+                        .unwrap_or_default(),
+                    path.display()
+                )],
+            )]
+        } else {
+            vec![ActionDescription::new(
+                format!("Delete file `{}`", path.display()),
+                vec![format!(
+                    "Delete file `{}` ({} bytes)",
+                    path.display(),
+                    self.buf.len()
+                )],
+            )]
+        };
+        for dir in created_parents.iter().rev() {
+            descriptions.push(ActionDescription::new(
+                format!("Remove directory `{}` if empty", dir.display()),
+                vec![],
+            ));
+        }
+        descriptions
     }
 
     #[tracing::instrument(level = "debug", skip_all)]
@@ -264,22 +921,215 @@ impl Action for CreateFile {
             group: _,
             mode: _,
             buf: _,
+            expected_hash: _,
             force: _,
+            create_parents: _,
+            prior,
+            created_parents: _,
+            block,
+            confirm_overwrite: _,
         } = self;
-        // The user already deleted it
-        if !path.exists() {
+
+        if block.is_some() {
+            self.revert_block().await?;
+        } else if let Some(prior) = prior {
+            let temp_file_path = temp_file_path(path);
+            if let Err(err) = write_temp_file(
+                &temp_file_path,
+                path,
+                &prior.buf,
+                Some(Uid::from_raw(prior.uid)),
+                Some(Gid::from_raw(prior.gid)),
+                Some(prior.mode),
+            )
+            .await
+            {
+                remove_temp_file(&temp_file_path).await;
+                return Err(Self::error(err));
+            }
             return Ok(());
+        } else if path.exists() {
+            // The user may have already deleted it
+            remove_file(&path)
+                .await
+                .map_err(|e| ActionErrorKind::Remove(path.to_owned(), e))
+                .map_err(Self::error)?;
         }
 
-        remove_file(&path)
-            .await
-            .map_err(|e| ActionErrorKind::Remove(path.to_owned(), e))
-            .map_err(Self::error)?;
+        for dir in self.created_parents.iter().rev() {
+            let is_empty = match dir.read_dir() {
+                Ok(mut entries) => entries.next().is_none(),
+                // The user already deleted it
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(Self::error(ActionErrorKind::Read(dir.clone(), e))),
+            };
+            if !is_empty {
+                tracing::debug!("Not removing `{}`, the folder is not empty", dir.display());
+                continue;
+            }
+            tokio::fs::remove_dir(dir)
+                .await
+                .map_err(|e| ActionErrorKind::Remove(dir.clone(), e))
+                .map_err(Self::error)?;
+        }
 
         Ok(())
     }
 }
 
+#[non_exhaustive]
+#[derive(Debug, thiserror::Error, strum::IntoStaticStr)]
+pub enum CreateFileError {
+    #[error("Overwriting `{}` was declined", .path.display())]
+    OverwriteDeclined { path: PathBuf },
+    #[error("Template placeholder `{placeholder}` has no substitution")]
+    UnresolvedPlaceholder { placeholder: String },
+}
+
+impl CreateFileError {
+    /// Always `false`, since the user declining or a bad template will not change on retry
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::OverwriteDeclined { .. } | Self::UnresolvedPlaceholder { .. } => false,
+        }
+    }
+}
+
+/// Replace each `{{key}}` in `template` with its value in `substitutions`, in a single pass
+fn render_template(
+    template: &str,
+    substitutions: &[(&str, &str)],
+) -> Result<String, CreateFileError> {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            return Err(CreateFileError::UnresolvedPlaceholder {
+                placeholder: rest[start..].lines().next().unwrap_or_default().to_string(),
+            });
+        };
+        let key = after[..end].trim();
+        match substitutions.iter().find(|(name, _)| *name == key) {
+            Some((_, value)) => rendered.push_str(value),
+            None => {
+                return Err(CreateFileError::UnresolvedPlaceholder {
+                    placeholder: format!("{{{{{key}}}}}"),
+                })
+            },
+        }
+        rest = &after[end + 2..];
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
+
+impl From<CreateFileError> for ActionErrorKind {
+    fn from(val: CreateFileError) -> Self {
+        ActionErrorKind::Custom(Box::new(val))
+    }
+}
+
+/// The SHA-256 digest of `buf`, as lowercase hex
+pub(crate) fn sha256_hex(buf: &[u8]) -> String {
+    ring::digest::digest(&ring::digest::SHA256, buf)
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Verify the SHA-256 digest of `buf`, accepting either `sha256:<hex>` or a bare hex digest
+pub(crate) fn verify_sha256(
+    path: &Path,
+    buf: &[u8],
+    expected_hash: &str,
+) -> Result<(), ActionErrorKind> {
+    let expected = expected_hash
+        .strip_prefix("sha256:")
+        .unwrap_or(expected_hash)
+        .to_ascii_lowercase();
+    if expected.len() != 64 || !expected.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(ActionErrorKind::InvalidHash(expected_hash.to_string()));
+    }
+
+    let found = sha256_hex(buf);
+
+    if found != expected {
+        return Err(ActionErrorKind::HashMismatch {
+            path: path.to_path_buf(),
+            expected: format!("sha256:{expected}"),
+            found: format!("sha256:{found}"),
+        });
+    }
+    Ok(())
+}
+
+/// A temporary file in the same directory as `path`, so that it can be renamed over `path` atomically
+pub(super) fn temp_file_path(path: &Path) -> PathBuf {
+    let parent_dir = path.parent().expect("File must be in a directory");
+    let mut rng = rand::thread_rng();
+    parent_dir.join(format!("nix-installer-tmp.{}", rng.gen::<u32>()))
+}
+
+pub(super) async fn remove_temp_file(temp_file_path: &Path) {
+    if let Err(e) = remove_file(temp_file_path).await {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            tracing::warn!(
+                "Could not remove temporary file `{}`: {e}",
+                temp_file_path.display()
+            );
+        }
+    }
+}
+
+/// Write `buf` to `temp_file_path` with the desired ownership and mode, then rename it over `path`
+pub(super) async fn write_temp_file(
+    temp_file_path: &Path,
+    path: &Path,
+    buf: &str,
+    uid: Option<Uid>,
+    gid: Option<Gid>,
+    mode: Option<u32>,
+) -> Result<(), ActionErrorKind> {
+    let mut temp_file = OpenOptions::new()
+        .create_new(true)
+        .write(true)
+        // Ensure the file has harmless permissions until the mode is
+        // applied below
+        .mode(0o600)
+        .open(&temp_file_path)
+        .await
+        .map_err(|e| ActionErrorKind::Open(temp_file_path.to_owned(), e))?;
+
+    temp_file
+        .write_all(buf.as_bytes())
+        .await
+        .map_err(|e| ActionErrorKind::Write(temp_file_path.to_owned(), e))?;
+    temp_file
+        .sync_all()
+        .await
+        .map_err(|e| ActionErrorKind::Sync(temp_file_path.to_owned(), e))?;
+
+    // Change ownership _before_ applying mode, to ensure that if
+    // a file needs to be setuid it will never be setuid for the
+    // wrong user
+    chown(temp_file_path, uid, gid).map_err(|e| ActionErrorKind::Chown(path.to_owned(), e))?;
+
+    if let Some(mode) = mode {
+        tokio::fs::set_permissions(&temp_file_path, PermissionsExt::from_mode(mode))
+            .await
+            .map_err(|e| ActionErrorKind::SetPermissions(mode, path.to_owned(), e))?;
+    }
+
+    tokio::fs::rename(&temp_file_path, &path)
+        .await
+        .map_err(|e| ActionErrorKind::Rename(temp_file_path.to_owned(), path.to_owned(), e))?;
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -464,6 +1314,142 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn creates_file_without_leaving_temporary_files() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let test_file = temp_dir
+            .path()
+            .join("creates_file_without_leaving_temporary_files");
+        let mut action = CreateFile::plan(
+            test_file.clone(),
+            None,
+            None,
+            Some(0o644),
+            "Test".into(),
+            false,
+        )
+        .await?;
+
+        action.try_execute().await?;
+
+        assert_eq!(tokio::fs::read_to_string(&test_file).await?, "Test");
+        let mode = tokio::fs::metadata(&test_file).await?.permissions().mode() & 0o777;
+        assert_eq!(mode, 0o644);
+        let entries = std::fs::read_dir(temp_dir.path())?.count();
+        assert_eq!(entries, 1, "Temporary file should have been renamed");
+
+        action.try_revert().await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn accepts_matching_hash() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let test_file = temp_dir.path().join("accepts_matching_hash");
+        let mut action = CreateFile::plan_with_options(
+            test_file.clone(),
+            None,
+            None,
+            None,
+            "Test".into(),
+            false,
+            CreateFileOptions {
+                expected_hash: Some(
+                    "sha256:532eaabd9574880dbf76b9b8cc00832c20a6ec113d682299550d7a6e0f345e25"
+                        .into(),
+                ),
+                ..Default::default()
+            },
+        )
+        .await?;
+
+        action.try_execute().await?;
+
+        action.try_revert().await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rejects_mismatched_hash() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let test_file = temp_dir.path().join("rejects_mismatched_hash");
+
+        match CreateFile::plan_with_options(
+            test_file.clone(),
+            None,
+            None,
+            None,
+            "Tampered".into(),
+            false,
+            CreateFileOptions {
+                expected_hash: Some(
+                    "532eaabd9574880dbf76b9b8cc00832c20a6ec113d682299550d7a6e0f345e25".into(),
+                ),
+                ..Default::default()
+            },
+        )
+        .await
+        {
+            Err(err) => match err.kind() {
+                ActionErrorKind::HashMismatch { path, .. } => assert_eq!(path, &test_file),
+                _ => {
+                    return Err(eyre!(
+                        "Should have returned an ActionErrorKind::HashMismatch error"
+                    ))
+                },
+            },
+            _ => {
+                return Err(eyre!(
+                    "Should have returned an ActionErrorKind::HashMismatch error"
+                ))
+            },
+        }
+
+        assert!(!test_file.exists(), "File should not have been created");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn dry_run_does_not_create_file() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let test_file = temp_dir.path().join("dry_run_does_not_create_file");
+        let action =
+            CreateFile::plan(test_file.clone(), None, None, None, "Test".into(), false).await?;
+
+        action.try_dry_run().await;
+
+        assert!(!test_file.exists(), "File should not have been created");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn describes_planned_mode() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let test_file = temp_dir.path().join("describes_planned_mode");
+
+        let action = CreateFile::plan(
+            test_file.clone(),
+            None,
+            None,
+            Some(0o644),
+            "Some content".into(),
+            false,
+        )
+        .await?;
+
+        let description = action.describe_execute();
+        assert_eq!(description.len(), 1);
+        assert!(description[0]
+            .explanation
+            .contains(&"Mode: `0o644`".to_string()));
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn errors_on_dir() -> eyre::Result<()> {
         let temp_dir = tempfile::tempdir()?;
@@ -495,4 +1481,303 @@ mod test {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn verify_detects_edited_file() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let test_file = temp_dir.path().join("verify_detects_edited_file");
+        let mut action =
+            CreateFile::plan(test_file.clone(), None, None, 0o644, "Test".into(), false).await?;
+
+        action.try_execute().await?;
+        action.inner().verify().await?;
+
+        write(test_file.as_path(), "More content").await?;
+
+        match action.inner().verify().await {
+            Err(err) => match err.kind() {
+                ActionErrorKind::DifferentContent(path) => assert_eq!(path, test_file.as_path()),
+                _ => {
+                    return Err(eyre!(
+                        "Should have returned an ActionErrorKind::DifferentContent error"
+                    ))
+                },
+            },
+            _ => {
+                return Err(eyre!(
+                    "Should have returned an ActionErrorKind::DifferentContent error"
+                ))
+            },
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn restores_forcibly_overwritten_file() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let test_file = temp_dir.path().join("restores_forcibly_overwritten_file");
+        write(test_file.as_path(), "User content").await?;
+        tokio::fs::set_permissions(&test_file, PermissionsExt::from_mode(0o600)).await?;
+
+        let mut action =
+            CreateFile::plan(test_file.clone(), None, None, 0o644, "Test".into(), true).await?;
+
+        action.try_execute().await?;
+        assert_eq!(tokio::fs::read_to_string(&test_file).await?, "Test");
+
+        action.try_revert().await?;
+
+        assert_eq!(tokio::fs::read_to_string(&test_file).await?, "User content");
+        let mode = tokio::fs::metadata(&test_file).await?.permissions().mode() & 0o777;
+        assert_eq!(mode, 0o600);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn creates_and_removes_parent_directories() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let parent = temp_dir
+            .path()
+            .join("creates_and_removes_parent_directories");
+        let test_file = parent.join("nested").join("file");
+        let mut action = CreateFile::plan_with_options(
+            test_file.clone(),
+            None,
+            None,
+            None,
+            "Test".into(),
+            false,
+            CreateFileOptions {
+                create_parents: true,
+                ..Default::default()
+            },
+        )
+        .await?;
+
+        action.try_execute().await?;
+        assert!(test_file.exists(), "File should have been created");
+
+        action.try_revert().await?;
+
+        assert!(
+            !parent.exists(),
+            "Parent directories should have been deleted"
+        );
+        assert!(temp_dir.path().exists(), "Existing directory should remain");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn replaces_and_removes_only_marker_block() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let test_file = temp_dir
+            .path()
+            .join("replaces_and_removes_only_marker_block");
+        let original = "# Before\n# >>> nix-installer\nOld\n# <<< nix-installer\n# After\n";
+        write(test_file.as_path(), original).await?;
+
+        let mut action = CreateFile::plan_block(
+            test_file.clone(),
+            None,
+            None,
+            None,
+            "New".into(),
+            BlockMarkers::default(),
+            false,
+        )
+        .await?;
+        action.try_execute().await?;
+        assert_eq!(
+            tokio::fs::read_to_string(&test_file).await?,
+            "# Before\n# >>> nix-installer\nNew\n# <<< nix-installer\n# After\n"
+        );
+
+        let replanned = CreateFile::plan_block(
+            test_file.clone(),
+            None,
+            None,
+            None,
+            "New".into(),
+            BlockMarkers::default(),
+            false,
+        )
+        .await?;
+        assert_eq!(replanned.state, crate::action::ActionState::Completed);
+
+        action.try_revert().await?;
+        assert_eq!(
+            tokio::fs::read_to_string(&test_file).await?,
+            "# Before\n# After\n"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn refuses_nested_markers() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let test_file = temp_dir.path().join("refuses_nested_markers");
+        write(
+            test_file.as_path(),
+            "# >>> nix-installer\n# >>> nix-installer\n# <<< nix-installer\n",
+        )
+        .await?;
+
+        match CreateFile::plan_block(
+            test_file.clone(),
+            None,
+            None,
+            None,
+            "New".into(),
+            BlockMarkers::default(),
+            false,
+        )
+        .await
+        {
+            Err(err) => {
+                assert!(matches!(
+                    err.kind(),
+                    ActionErrorKind::MalformedMarkers { .. }
+                ));
+                assert_eq!(err.code(), "MalformedMarkers");
+            },
+            Ok(_) => return Err(eyre!("Nested markers should have been refused")),
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn declined_overwrite_keeps_file() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let test_file = temp_dir.path().join("declined_overwrite_keeps_file");
+        write(test_file.as_path(), "Original").await?;
+
+        let mut action = CreateFile::plan(
+            test_file.clone(),
+            None,
+            None,
+            None,
+            "Replacement".into(),
+            true,
+        )
+        .await?
+        .with_overwrite_confirmation(|_| false);
+
+        match action.try_execute().await {
+            Err(err) => {
+                let ActionErrorKind::Custom(err) = err.kind() else {
+                    return Err(eyre!("Should have returned a CreateFileError"));
+                };
+                assert!(matches!(
+                    err.downcast_ref::<CreateFileError>(),
+                    Some(CreateFileError::OverwriteDeclined { path }) if *path == test_file
+                ));
+            },
+            Ok(_) => return Err(eyre!("Overwriting should have been declined")),
+        }
+        assert_eq!(tokio::fs::read_to_string(&test_file).await?, "Original");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reports_pending_changes() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let test_file = temp_dir.path().join("reports_pending_changes");
+        let action = CreateFile::plan(
+            test_file.clone(),
+            None,
+            None,
+            Some(0o644),
+            "Test".into(),
+            true,
+        )
+        .await?;
+        assert_eq!(action.inner().pending_change(), Some(FileChange::Missing));
+
+        write(test_file.as_path(), "Test").await?;
+        tokio::fs::set_permissions(test_file.as_path(), PermissionsExt::from_mode(0o600)).await?;
+        assert_eq!(
+            action.inner().pending_change(),
+            Some(FileChange::Mode {
+                found: 0o600,
+                planned: 0o644
+            })
+        );
+
+        tokio::fs::set_permissions(test_file.as_path(), PermissionsExt::from_mode(0o644)).await?;
+        assert!(!action.inner().would_change());
+
+        write(test_file.as_path(), "Edited").await?;
+        assert_eq!(action.inner().pending_change(), Some(FileChange::Content));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn completes_without_executing_when_already_satisfied() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let test_file = temp_dir
+            .path()
+            .join("completes_without_executing_when_already_satisfied");
+        let mut action =
+            CreateFile::plan(test_file.clone(), None, None, None, "Test".into(), false).await?;
+
+        // Written by an earlier run, after this plan was made
+        write(test_file.as_path(), "Test").await?;
+        action.try_execute().await?;
+        assert_eq!(action.state, crate::action::ActionState::Completed);
+        assert_eq!(action.elapsed(), None, "Execute should not have run");
+
+        // Like a file which already matched when planning
+        action.try_revert().await?;
+        assert!(!test_file.exists(), "File should have been deleted");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reports_current_against_desired_state() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let test_file = temp_dir
+            .path()
+            .join("reports_current_against_desired_state");
+        let action =
+            CreateFile::plan(test_file.clone(), None, None, None, "Test".into(), false).await?;
+
+        let report = action.state_report().await?;
+        assert!(!report.satisfied);
+        assert_eq!(report.current, None);
+        assert_eq!(report.desired.as_deref(), Some("Test"));
+        assert_eq!(report.reasons.len(), 1);
+
+        write(test_file.as_path(), "Test").await?;
+        let report = action.state_report().await?;
+        assert!(report.satisfied);
+        assert_eq!(report.current.as_deref(), Some("Test"));
+        assert!(report.reasons.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn renders_templates() {
+        assert_eq!(
+            render_template(
+                "{{ name }} {{url}}\n",
+                &[("name", "nixpkgs"), ("url", "{{https://example.com}}")]
+            )
+            .unwrap(),
+            "nixpkgs {{https://example.com}}\n"
+        );
+        assert!(matches!(
+            render_template("{{missing}}", &[]),
+            Err(CreateFileError::UnresolvedPlaceholder { placeholder }) if placeholder == "{{missing}}"
+        ));
+        assert!(render_template("{{unterminated", &[]).is_err());
+    }
 }
@@ -153,6 +153,9 @@ impl Action for CreateOrInsertIntoFile {
     fn action_tag() -> ActionTag {
         ActionTag("create_or_insert_into_file")
     }
+    fn paths_written(&self) -> Vec<PathBuf> {
+        vec![self.path.clone()]
+    }
     fn tracing_synopsis(&self) -> String {
         format!("Create or insert file `{}`", self.path.display())
     }
@@ -208,6 +211,7 @@ impl Action for CreateOrInsertIntoFile {
         }
         let mut temp_file = OpenOptions::new()
             .create(true)
+            .truncate(true)
             .write(true)
             // If the file is created, ensure that it has harmless
             // permissions regardless of whether the mode will be
@@ -311,6 +315,19 @@ impl Action for CreateOrInsertIntoFile {
         Ok(())
     }
 
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn dry_run(&self) {
+        tracing::info!(
+            "Would insert {} bytes at the {} of `{}`",
+            self.buf.len(),
+            match self.position {
+                Position::Beginning => "beginning",
+                Position::End => "end",
+            },
+            self.path.display()
+        );
+    }
+
     fn revert_description(&self) -> Vec<ActionDescription> {
         let Self {
             path,
@@ -323,7 +340,8 @@ impl Action for CreateOrInsertIntoFile {
         vec![ActionDescription::new(
             format!("Delete Nix related fragment from file `{}`", path.display()),
             vec![format!(
-                "Delete Nix related fragment from file `{}`. Fragment: `{buf}`",
+                "Delete the {} byte Nix related fragment from file `{}`, deleting the file if nothing else remains. Fragment: `{buf}`",
+                buf.len(),
                 path.display()
             )],
         )]
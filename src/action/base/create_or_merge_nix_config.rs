@@ -22,7 +22,7 @@ const NIX_CONF_MODE: u32 = 0o664;
 const NIX_CONF_COMMENT_CHAR: char = '#';
 
 #[non_exhaustive]
-#[derive(Debug, thiserror::Error)]
+#[derive(Debug, thiserror::Error, strum::IntoStaticStr)]
 pub enum CreateOrMergeNixConfigError {
     #[error(transparent)]
     ParseNixConfig(#[from] nix_config_parser::ParseError),
@@ -180,6 +180,9 @@ impl Action for CreateOrMergeNixConfig {
     fn action_tag() -> ActionTag {
         ActionTag("create_or_merge_nix_config")
     }
+    fn paths_written(&self) -> Vec<PathBuf> {
+        vec![self.path.clone()]
+    }
     fn tracing_synopsis(&self) -> String {
         format!(
             "Merge or create nix.conf file `{path}`",
@@ -199,8 +202,7 @@ impl Action for CreateOrMergeNixConfig {
         if tracing::enabled!(tracing::Level::TRACE) {
             span.record(
                 "pending_nix_config",
-                &self
-                    .pending_nix_config
+                self.pending_nix_config
                     .settings()
                     .iter()
                     .map(|(k, v)| format!("{k}=\"{v}\""))
@@ -258,6 +260,7 @@ impl Action for CreateOrMergeNixConfig {
         }
         let mut temp_file = OpenOptions::new()
             .create(true)
+            .truncate(true)
             .write(true)
             // If the file is created, ensure that it has harmless
             // permissions regardless of whether the mode will be
@@ -451,6 +454,15 @@ impl Action for CreateOrMergeNixConfig {
         Ok(())
     }
 
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn dry_run(&self) {
+        tracing::info!(
+            "Would merge {} settings into `{}`",
+            self.pending_nix_config.settings().len(),
+            self.path.display()
+        );
+    }
+
     fn revert_description(&self) -> Vec<ActionDescription> {
         let Self {
             path,
@@ -0,0 +1,377 @@
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use nix::unistd::{Gid, Uid};
+use tokio::fs::read_to_string;
+use tracing::{span, Span};
+
+use crate::action::base::backup_shell_profile::profiles_for;
+use crate::action::base::create_file::{remove_temp_file, temp_file_path, write_temp_file};
+use crate::action::{
+    Action, ActionDescription, ActionError, ActionErrorKind, ActionTag, StatefulAction,
+};
+use crate::planner::ShellProfileLocations;
+use crate::self_test::Shell;
+
+const BLOCK_START: &str = "# Nix";
+const BLOCK_END: &str = "# End Nix";
+/// Every block [`ConfigureShellProfile`](crate::action::common::ConfigureShellProfile) writes sources one of these
+const MANAGED_PROFILE_PREFIX: &str = "/nix/var/nix/profiles/default/etc/profile.d/nix";
+
+/** Collapse repeated `# Nix` ... `# End Nix` blocks in the profile files of a [`Shell`], such as
+those left by installing more than once, down to the first one.
+
+Only blocks sourcing the Nix profile are touched, anything else in the files is left alone.
+ */
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone)]
+pub struct DedupeShellProfile {
+    shell: Shell,
+    profiles: Vec<PathBuf>,
+    /// The duplicates removed during execute, so revert can put them back
+    removed: Vec<RemovedBlock>,
+}
+
+/// A duplicate block removed by [`DedupeShellProfile`]
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone, PartialEq, Eq)]
+pub struct RemovedBlock {
+    pub profile: PathBuf,
+    /// The line the block started on, before anything was removed
+    pub line: usize,
+    pub contents: String,
+}
+
+impl DedupeShellProfile {
+    #[tracing::instrument(level = "debug", skip_all)]
+    pub async fn plan(
+        shell: Shell,
+        locations: &ShellProfileLocations,
+    ) -> Result<StatefulAction<Self>, ActionError> {
+        let mut profiles = vec![];
+        // Some tools (eg `nix-darwin`) create symlinks to these files, they are not ours to edit.
+        for profile in profiles_for(shell, locations)
+            .into_iter()
+            .filter(|profile| profile.is_file() && !profile.is_symlink())
+        {
+            let contents = read_to_string(&profile)
+                .await
+                .map_err(|e| Self::error(ActionErrorKind::Read(profile.clone(), e)))?;
+            if !collapse_duplicates(&contents).1.is_empty() {
+                profiles.push(profile);
+            }
+        }
+
+        let this = Self {
+            shell,
+            profiles,
+            removed: vec![],
+        };
+
+        if this.profiles.is_empty() {
+            tracing::debug!("No duplicate Nix blocks in the `{shell}` profiles");
+            return Ok(StatefulAction::skipped(this));
+        }
+
+        Ok(StatefulAction::uncompleted(this))
+    }
+}
+
+/// Remove every managed block which repeats an earlier one, returning the remaining contents and what was removed
+///
+/// The blank lines the installer writes after a block are removed along with it.
+fn collapse_duplicates(contents: &str) -> (String, Vec<(usize, String)>) {
+    let lines = contents.split_inclusive('\n').collect::<Vec<_>>();
+    let mut kept = String::with_capacity(contents.len());
+    let mut seen = vec![];
+    let mut removed = vec![];
+    let mut idx = 0;
+    while idx < lines.len() {
+        let Some(len) = managed_block_len(&lines[idx..]) else {
+            kept.push_str(lines[idx]);
+            idx += 1;
+            continue;
+        };
+        let block = lines[idx..idx + len].concat();
+        if !seen.contains(&block) {
+            kept.push_str(&block);
+            seen.push(block);
+            idx += len;
+            continue;
+        }
+        let trailing = lines[idx + len..]
+            .iter()
+            .take_while(|line| line.trim().is_empty())
+            .count();
+        removed.push((idx, lines[idx..idx + len + trailing].concat()));
+        idx += len + trailing;
+    }
+    (kept, removed)
+}
+
+/// The number of lines in the managed block `lines` starts with, if it starts with one
+fn managed_block_len(lines: &[&str]) -> Option<usize> {
+    if lines.first()?.trim_end() != BLOCK_START {
+        return None;
+    }
+    let end = lines.iter().position(|line| line.trim_end() == BLOCK_END)?;
+    lines[..end]
+        .iter()
+        .any(|line| line.contains(MANAGED_PROFILE_PREFIX))
+        .then_some(end + 1)
+}
+
+/// Put `removed` blocks back where [`collapse_duplicates`] took them from
+fn restore_duplicates(contents: &str, removed: &[&RemovedBlock]) -> String {
+    let mut lines = contents
+        .split_inclusive('\n')
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    let mut removed = removed.to_vec();
+    removed.sort_by_key(|block| block.line);
+    for block in removed {
+        // Later blocks were numbered with the earlier ones still in place, so each must be restored line by line
+        let line = block.line.min(lines.len());
+        lines.splice(
+            line..line,
+            block
+                .contents
+                .split_inclusive('\n')
+                .map(ToString::to_string),
+        );
+    }
+    lines.concat()
+}
+
+/// Atomically replace the contents of `profile`, keeping its ownership and mode
+async fn rewrite_profile(profile: &Path, contents: &str) -> Result<(), ActionErrorKind> {
+    let metadata = tokio::fs::metadata(profile)
+        .await
+        .map_err(|e| ActionErrorKind::GettingMetadata(profile.to_path_buf(), e))?;
+    let temp_file_path = temp_file_path(profile);
+    if let Err(err) = write_temp_file(
+        &temp_file_path,
+        profile,
+        contents,
+        Some(Uid::from_raw(metadata.uid())),
+        Some(Gid::from_raw(metadata.gid())),
+        Some(metadata.permissions().mode() & 0o7777),
+    )
+    .await
+    {
+        remove_temp_file(&temp_file_path).await;
+        return Err(err);
+    }
+    Ok(())
+}
+
+#[async_trait::async_trait]
+#[typetag::serde(name = "dedupe_shell_profile")]
+impl Action for DedupeShellProfile {
+    fn action_tag() -> ActionTag {
+        ActionTag("dedupe_shell_profile")
+    }
+    fn category(&self) -> crate::action::ActionCategory {
+        crate::action::ActionCategory::Configuration
+    }
+    fn paths_written(&self) -> Vec<PathBuf> {
+        self.profiles.clone()
+    }
+    fn tracing_synopsis(&self) -> String {
+        format!(
+            "Remove duplicate Nix blocks from the `{}` profiles",
+            self.shell
+        )
+    }
+
+    fn tracing_span(&self) -> Span {
+        span!(
+            tracing::Level::DEBUG,
+            "dedupe_shell_profile",
+            shell = tracing::field::display(self.shell),
+        )
+    }
+
+    fn execute_description(&self) -> Vec<ActionDescription> {
+        vec![ActionDescription::new(
+            self.tracing_synopsis(),
+            self.profiles
+                .iter()
+                .map(|profile| format!("Keep only the first Nix block in `{}`", profile.display()))
+                .collect(),
+        )]
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn execute(&mut self) -> Result<(), ActionError> {
+        let Self {
+            shell: _,
+            profiles,
+            removed,
+        } = self;
+
+        for profile in profiles.iter() {
+            // The user may have removed it since planning
+            if !profile.exists() {
+                continue;
+            }
+            let contents = read_to_string(profile)
+                .await
+                .map_err(|e| Self::error(ActionErrorKind::Read(profile.clone(), e)))?;
+            let (kept, blocks) = collapse_duplicates(&contents);
+            if blocks.is_empty() {
+                continue;
+            }
+            rewrite_profile(profile, &kept).await.map_err(Self::error)?;
+            removed.extend(blocks.into_iter().map(|(line, contents)| RemovedBlock {
+                profile: profile.clone(),
+                line,
+                contents,
+            }));
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn dry_run(&self) {
+        for profile in &self.profiles {
+            tracing::info!(
+                "Would remove duplicate Nix blocks from `{}`",
+                profile.display()
+            );
+        }
+    }
+
+    fn revert_description(&self) -> Vec<ActionDescription> {
+        vec![ActionDescription::new(
+            format!(
+                "Restore the duplicate Nix blocks in the `{}` profiles",
+                self.shell
+            ),
+            self.removed
+                .iter()
+                .map(|block| {
+                    format!(
+                        "Restore the block at line {} of `{}`",
+                        block.line + 1,
+                        block.profile.display()
+                    )
+                })
+                .collect(),
+        )]
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn revert(&mut self) -> Result<(), ActionError> {
+        let mut errors = vec![];
+
+        for profile in self.profiles.clone() {
+            let blocks = self
+                .removed
+                .iter()
+                .filter(|block| block.profile == profile)
+                .collect::<Vec<_>>();
+            if blocks.is_empty() {
+                continue;
+            }
+            match restore_profile(&profile, &blocks).await {
+                Ok(()) => self.removed.retain(|block| block.profile != profile),
+                Err(err) => errors.push(Self::error(err)),
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else if errors.len() == 1 {
+            Err(errors
+                .into_iter()
+                .next()
+                .expect("Expected 1 len Vec to have at least 1 item"))
+        } else {
+            Err(Self::error(ActionErrorKind::MultipleChildren(errors)))
+        }
+    }
+}
+
+async fn restore_profile(profile: &Path, blocks: &[&RemovedBlock]) -> Result<(), ActionErrorKind> {
+    // The user removed it, so there is nothing to restore into
+    if !profile.exists() {
+        return Ok(());
+    }
+    let contents = read_to_string(profile)
+        .await
+        .map_err(|e| ActionErrorKind::Read(profile.to_path_buf(), e))?;
+    rewrite_profile(profile, &restore_duplicates(&contents, blocks)).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const BLOCK: &str = "# Nix\nif [ -e '/nix/var/nix/profiles/default/etc/profile.d/nix-daemon.sh' ]; then\n    . '/nix/var/nix/profiles/default/etc/profile.d/nix-daemon.sh'\nfi\n# End Nix\n";
+
+    #[test]
+    fn only_collapses_managed_blocks() {
+        let user_block = "# Nix\nexport EDITOR=vim\n# End Nix\n";
+        let contents =
+            format!("{BLOCK}\n\n# Mine\n{user_block}{BLOCK}\n\n{user_block}{BLOCK}alias x=y\n");
+        let (kept, removed) = collapse_duplicates(&contents);
+        assert_eq!(
+            kept,
+            format!("{BLOCK}\n\n# Mine\n{user_block}{user_block}alias x=y\n")
+        );
+        assert_eq!(removed.len(), 2);
+        assert_eq!(
+            collapse_duplicates(&kept).1,
+            vec![],
+            "Collapsing is idempotent"
+        );
+    }
+
+    #[test]
+    fn restores_several_blocks() {
+        let original = format!("# Top\n{BLOCK}\n{BLOCK}\n\n# Middle\n{BLOCK}# Bottom\n");
+        let (kept, removed) = collapse_duplicates(&original);
+        assert_eq!(removed.len(), 2);
+        let removed = removed
+            .into_iter()
+            .map(|(line, contents)| RemovedBlock {
+                profile: PathBuf::from("bashrc"),
+                line,
+                contents,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            restore_duplicates(&kept, &removed.iter().collect::<Vec<_>>()),
+            original
+        );
+    }
+
+    #[tokio::test]
+    async fn removes_and_restores_duplicates() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let bashrc = temp_dir.path().join("bashrc");
+        let original = format!("# Top\n{BLOCK}\n# Middle\n{BLOCK}\n\n# Bottom\n");
+        tokio::fs::write(&bashrc, &original).await?;
+        tokio::fs::set_permissions(&bashrc, PermissionsExt::from_mode(0o640)).await?;
+
+        let locations = ShellProfileLocations {
+            bash: vec![bashrc.clone()],
+            ..Default::default()
+        };
+        let mut action = DedupeShellProfile::plan(Shell::Bash, &locations).await?;
+        action.try_execute().await?;
+        assert_eq!(
+            read_to_string(&bashrc).await?,
+            format!("# Top\n{BLOCK}\n# Middle\n# Bottom\n")
+        );
+
+        assert_eq!(
+            tokio::fs::metadata(&bashrc).await?.permissions().mode() & 0o777,
+            0o640
+        );
+
+        action.try_revert().await?;
+        assert_eq!(read_to_string(&bashrc).await?, original);
+        Ok(())
+    }
+}
@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, time::Duration};
 
 use bytes::{Buf, Bytes};
 use reqwest::Url;
@@ -6,12 +6,20 @@ use tracing::{span, Span};
 
 use crate::{
     action::{Action, ActionDescription, ActionError, ActionErrorKind, ActionTag, StatefulAction},
-    parse_ssl_cert,
+    http_client, parse_ssl_cert,
     settings::UrlOrPath,
 };
 
+/// A rough guess at how long downloading a Nix tarball takes, since its size is not known up front
+const ESTIMATED_DOWNLOAD_DURATION: Duration = Duration::from_secs(30);
+/// A rough guess at how quickly a local tarball is decompressed and unpacked
+const ESTIMATED_UNPACK_BYTES_PER_SEC: f64 = 20_000_000.0;
+
 /**
 Fetch a URL to the given path
+
+If no `client` is provided, one is built from `proxy`, `ssl_cert_file`, and `timeout`. A provided
+`client` is not serialized, so a plan loaded from a receipt falls back to building one.
 */
 #[derive(Debug, serde::Deserialize, serde::Serialize, Clone)]
 pub struct FetchAndUnpackNix {
@@ -19,6 +27,10 @@ pub struct FetchAndUnpackNix {
     dest: PathBuf,
     proxy: Option<Url>,
     ssl_cert_file: Option<PathBuf>,
+    #[serde(default)]
+    timeout: Option<Duration>,
+    #[serde(skip)]
+    client: Option<reqwest::Client>,
 }
 
 impl FetchAndUnpackNix {
@@ -28,6 +40,8 @@ impl FetchAndUnpackNix {
         dest: PathBuf,
         proxy: Option<Url>,
         ssl_cert_file: Option<PathBuf>,
+        timeout: Option<Duration>,
+        client: impl Into<Option<reqwest::Client>>,
     ) -> Result<StatefulAction<Self>, ActionError> {
         // TODO(@hoverbear): Check URL exists?
         // TODO(@hoverbear): Check tempdir exists
@@ -55,6 +69,8 @@ impl FetchAndUnpackNix {
             dest,
             proxy,
             ssl_cert_file,
+            timeout,
+            client: client.into(),
         }
         .into())
     }
@@ -66,10 +82,34 @@ impl Action for FetchAndUnpackNix {
     fn action_tag() -> ActionTag {
         ActionTag("fetch_and_unpack_nix")
     }
+    fn paths_written(&self) -> Vec<PathBuf> {
+        vec![self.dest.clone()]
+    }
+    fn paths_read(&self) -> Vec<PathBuf> {
+        match &self.url_or_path {
+            UrlOrPath::Path(path) => vec![path.clone()],
+            UrlOrPath::Url(_) => vec![],
+        }
+    }
     fn tracing_synopsis(&self) -> String {
         format!("Fetch `{}` to `{}`", self.url_or_path, self.dest.display())
     }
 
+    fn estimated_duration(&self) -> Option<Duration> {
+        let local_path = match &self.url_or_path {
+            UrlOrPath::Url(url) if url.scheme() == "file" => url.to_file_path().ok(),
+            UrlOrPath::Url(_) => None,
+            UrlOrPath::Path(path) => Some(path.clone()),
+        };
+        match local_path.and_then(|path| path.metadata().ok()) {
+            Some(metadata) => Some(Duration::from_secs_f64(
+                metadata.len() as f64 / ESTIMATED_UNPACK_BYTES_PER_SEC,
+            )),
+            // The size is not known until the response arrives
+            None => Some(ESTIMATED_DOWNLOAD_DURATION),
+        }
+    }
+
     fn tracing_span(&self) -> Span {
         let span = span!(
             tracing::Level::DEBUG,
@@ -101,23 +141,16 @@ impl Action for FetchAndUnpackNix {
             UrlOrPath::Url(url) => {
                 let bytes = match url.scheme() {
                     "https" | "http" => {
-                        let mut buildable_client = reqwest::Client::builder();
-                        if let Some(proxy) = &self.proxy {
-                            buildable_client = buildable_client.proxy(
-                                reqwest::Proxy::all(proxy.clone())
-                                    .map_err(ActionErrorKind::Reqwest)
-                                    .map_err(Self::error)?,
+                        let client = match &self.client {
+                            Some(client) => client.clone(),
+                            None => http_client(
+                                self.proxy.as_ref(),
+                                self.ssl_cert_file.as_deref(),
+                                self.timeout,
                             )
-                        }
-                        if let Some(ssl_cert_file) = &self.ssl_cert_file {
-                            let ssl_cert =
-                                parse_ssl_cert(ssl_cert_file).await.map_err(Self::error)?;
-                            buildable_client = buildable_client.add_root_certificate(ssl_cert);
-                        }
-                        let client = buildable_client
-                            .build()
-                            .map_err(ActionErrorKind::Reqwest)
-                            .map_err(Self::error)?;
+                            .await
+                            .map_err(Self::error)?,
+                        };
                         let req = client
                             .get(url.clone())
                             .build()
@@ -170,6 +203,20 @@ impl Action for FetchAndUnpackNix {
         Ok(())
     }
 
+    fn max_retries(&self) -> u32 {
+        // Unpacking over a partial previous attempt is harmless
+        3
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn dry_run(&self) {
+        tracing::info!(
+            "Would fetch `{}` and unpack it to `{}`",
+            self.url_or_path,
+            self.dest.display()
+        );
+    }
+
     fn revert_description(&self) -> Vec<ActionDescription> {
         vec![/* Deliberately empty -- this is a noop */]
     }
@@ -181,12 +228,24 @@ impl Action for FetchAndUnpackNix {
 }
 
 #[non_exhaustive]
-#[derive(Debug, thiserror::Error)]
+#[derive(Debug, thiserror::Error, strum::IntoStaticStr)]
 pub enum FetchUrlError {
     #[error("Unarchiving error")]
     Unarchive(#[source] std::io::Error),
     #[error("Unknown proxy scheme, `https://`, `socks5://`, and `http://` supported")]
     UnknownProxyScheme,
+    #[error("`{0}` does not point to a local file path")]
+    FileUrl(Url),
+}
+
+impl FetchUrlError {
+    /// Whether retrying could succeed, such as after a truncated download
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Unarchive(err) => crate::action::io_is_transient(err),
+            Self::UnknownProxyScheme | Self::FileUrl(_) => false,
+        }
+    }
 }
 
 impl From<FetchUrlError> for ActionErrorKind {
@@ -0,0 +1,249 @@
+use std::{path::PathBuf, time::Duration};
+
+use reqwest::Url;
+use tokio::{fs::remove_file, io::AsyncWriteExt, sync::mpsc::Sender};
+use tracing::{span, Span};
+
+use crate::{
+    action::{Action, ActionDescription, ActionError, ActionErrorKind, ActionTag, StatefulAction},
+    http_client, parse_ssl_cert,
+};
+
+use super::FetchUrlError;
+
+/// How much of a [`FetchUrl`] download has arrived
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FetchProgress {
+    pub downloaded: u64,
+    /// The size of the download, if the server reported it
+    pub total: Option<u64>,
+}
+
+/**
+Download a URL to the given path, removing it on revert
+
+If no `client` is provided, one is built from `proxy`, `ssl_cert_file`, and `timeout`. Like the `client`, a `progress`
+channel set with [`with_progress`](StatefulAction::<FetchUrl>::with_progress) is not serialized.
+*/
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone)]
+pub struct FetchUrl {
+    url: Url,
+    dest: PathBuf,
+    proxy: Option<Url>,
+    ssl_cert_file: Option<PathBuf>,
+    timeout: Option<Duration>,
+    #[serde(skip)]
+    client: Option<reqwest::Client>,
+    #[serde(skip)]
+    progress: Option<Sender<FetchProgress>>,
+}
+
+impl FetchUrl {
+    #[tracing::instrument(level = "debug", skip_all)]
+    pub async fn plan(
+        url: Url,
+        dest: PathBuf,
+        proxy: Option<Url>,
+        ssl_cert_file: Option<PathBuf>,
+        timeout: Option<Duration>,
+        client: impl Into<Option<reqwest::Client>>,
+    ) -> Result<StatefulAction<Self>, ActionError> {
+        match url.scheme() {
+            "https" | "http" | "file" => (),
+            _ => return Err(Self::error(ActionErrorKind::UnknownUrlScheme)),
+        }
+
+        if let Some(proxy) = &proxy {
+            match proxy.scheme() {
+                "https" | "http" | "socks5" => (),
+                _ => return Err(Self::error(FetchUrlError::UnknownProxyScheme)),
+            };
+        }
+
+        if let Some(ssl_cert_file) = &ssl_cert_file {
+            parse_ssl_cert(ssl_cert_file).await.map_err(Self::error)?;
+        }
+
+        if dest.exists() {
+            return Err(Self::error(ActionErrorKind::FileExists(dest)));
+        }
+
+        Ok(Self {
+            url,
+            dest,
+            proxy,
+            ssl_cert_file,
+            timeout,
+            client: client.into(),
+            progress: None,
+        }
+        .into())
+    }
+
+    /// Where the download is written
+    pub fn dest(&self) -> &PathBuf {
+        &self.dest
+    }
+}
+
+impl StatefulAction<FetchUrl> {
+    /// Send a [`FetchProgress`] along `progress` as each chunk of the download arrives
+    pub fn with_progress(mut self, progress: Sender<FetchProgress>) -> Self {
+        self.action.progress = Some(progress);
+        self
+    }
+}
+
+#[async_trait::async_trait]
+#[typetag::serde(name = "fetch_url")]
+impl Action for FetchUrl {
+    fn action_tag() -> ActionTag {
+        ActionTag("fetch_url")
+    }
+    fn paths_written(&self) -> Vec<PathBuf> {
+        vec![self.dest.clone()]
+    }
+    fn tracing_synopsis(&self) -> String {
+        format!("Fetch `{}` to `{}`", self.url, self.dest.display())
+    }
+
+    fn tracing_span(&self) -> Span {
+        let span = span!(
+            tracing::Level::DEBUG,
+            "fetch_url",
+            url = tracing::field::display(&self.url),
+            proxy = tracing::field::Empty,
+            ssl_cert_file = tracing::field::Empty,
+            dest = tracing::field::display(self.dest.display()),
+        );
+        if let Some(proxy) = &self.proxy {
+            span.record("proxy", tracing::field::display(&proxy));
+        }
+        if let Some(ssl_cert_file) = &self.ssl_cert_file {
+            span.record(
+                "ssl_cert_file",
+                tracing::field::display(&ssl_cert_file.display()),
+            );
+        }
+        span
+    }
+
+    fn execute_description(&self) -> Vec<ActionDescription> {
+        vec![ActionDescription::new(self.tracing_synopsis(), vec![])]
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn execute(&mut self) -> Result<(), ActionError> {
+        if self.url.scheme() == "file" {
+            let src = self
+                .url
+                .to_file_path()
+                .map_err(|()| Self::error(FetchUrlError::FileUrl(self.url.clone())))?;
+            tokio::fs::copy(&src, &self.dest)
+                .await
+                .map_err(|e| ActionErrorKind::Copy(src, self.dest.clone(), e))
+                .map_err(Self::error)?;
+            return Ok(());
+        }
+
+        let client = match &self.client {
+            Some(client) => client.clone(),
+            None => http_client(
+                self.proxy.as_ref(),
+                self.ssl_cert_file.as_deref(),
+                self.timeout,
+            )
+            .await
+            .map_err(Self::error)?,
+        };
+        let mut res = client
+            .get(self.url.clone())
+            .send()
+            .await
+            .and_then(|res| res.error_for_status())
+            .map_err(ActionErrorKind::Reqwest)
+            .map_err(Self::error)?;
+
+        // A partial previous attempt is overwritten
+        let mut file = tokio::fs::File::create(&self.dest)
+            .await
+            .map_err(|e| Self::error(ActionErrorKind::Open(self.dest.clone(), e)))?;
+        let mut progress = FetchProgress {
+            downloaded: 0,
+            total: res.content_length(),
+        };
+        while let Some(chunk) = res
+            .chunk()
+            .await
+            .map_err(ActionErrorKind::Reqwest)
+            .map_err(Self::error)?
+        {
+            file.write_all(&chunk)
+                .await
+                .map_err(|e| Self::error(ActionErrorKind::Write(self.dest.clone(), e)))?;
+            progress.downloaded += chunk.len() as u64;
+            if let Some(sender) = &self.progress {
+                // A UI which stopped listening should not fail the download
+                let _ = sender.send(progress).await;
+            }
+        }
+        file.sync_all()
+            .await
+            .map_err(|e| Self::error(ActionErrorKind::Sync(self.dest.clone(), e)))?;
+
+        Ok(())
+    }
+
+    fn max_retries(&self) -> u32 {
+        // Downloading over a partial previous attempt is harmless
+        3
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn dry_run(&self) {
+        tracing::info!("Would fetch `{}` to `{}`", self.url, self.dest.display());
+    }
+
+    fn revert_description(&self) -> Vec<ActionDescription> {
+        vec![ActionDescription::new(
+            format!("Remove the download `{}`", self.dest.display()),
+            vec![],
+        )]
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn revert(&mut self) -> Result<(), ActionError> {
+        // The user may have already removed it
+        if self.dest.exists() {
+            remove_file(&self.dest)
+                .await
+                .map_err(|e| Self::error(ActionErrorKind::Remove(self.dest.clone(), e)))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn fetches_file_urls_and_removes_on_revert() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        // Percent-encoded in the URL
+        let src = temp_dir.path().join("nix src");
+        let dest = temp_dir.path().join("dest");
+        tokio::fs::write(&src, "Test").await?;
+        let url = Url::from_file_path(&src).map_err(|()| eyre::eyre!("Not absolute"))?;
+        assert!(url.path().ends_with("/nix%20src"), "{url}");
+
+        let mut action = FetchUrl::plan(url, dest.clone(), None, None, None, None).await?;
+        action.try_execute().await?;
+        assert_eq!(tokio::fs::read_to_string(&dest).await?, "Test");
+
+        action.try_revert().await?;
+        assert!(!dest.exists(), "Download should be removed");
+
+        Ok(())
+    }
+}
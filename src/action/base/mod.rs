@@ -1,27 +1,41 @@
 //! Base [`Action`](crate::action::Action)s that themselves have no other actions as dependencies
 
 pub(crate) mod add_user_to_group;
+pub(crate) mod backup_shell_profile;
+pub(crate) mod check_nix_store;
 pub(crate) mod create_directory;
 pub(crate) mod create_file;
 pub(crate) mod create_group;
 pub(crate) mod create_or_insert_into_file;
 pub(crate) mod create_or_merge_nix_config;
 pub(crate) mod create_user;
+pub(crate) mod dedupe_shell_profile;
 pub(crate) mod delete_user;
 pub(crate) mod fetch_and_unpack_nix;
+#[cfg(feature = "fetch-url")]
+pub(crate) mod fetch_url;
 pub(crate) mod move_unpacked_nix;
 pub(crate) mod remove_directory;
+pub(crate) mod remove_file;
 pub(crate) mod setup_default_profile;
 
 pub use add_user_to_group::AddUserToGroup;
+pub use backup_shell_profile::BackupShellProfile;
+pub use check_nix_store::{CheckNixStore, CheckNixStoreError};
 pub use create_directory::CreateDirectory;
-pub use create_file::CreateFile;
+pub use create_file::{
+    BlockMarkers, CreateFile, CreateFileError, CreateFileOptions, FileChange, OverwriteConfirmation,
+};
 pub use create_group::CreateGroup;
 pub use create_or_insert_into_file::CreateOrInsertIntoFile;
 pub use create_or_merge_nix_config::CreateOrMergeNixConfig;
 pub use create_user::CreateUser;
+pub use dedupe_shell_profile::{DedupeShellProfile, RemovedBlock};
 pub use delete_user::DeleteUser;
 pub use fetch_and_unpack_nix::{FetchAndUnpackNix, FetchUrlError};
+#[cfg(feature = "fetch-url")]
+pub use fetch_url::{FetchProgress, FetchUrl};
 pub use move_unpacked_nix::{MoveUnpackedNix, MoveUnpackedNixError};
 pub use remove_directory::RemoveDirectory;
+pub use remove_file::{RemoveFile, RemoveFileError};
 pub use setup_default_profile::{SetupDefaultProfile, SetupDefaultProfileError};
@@ -34,6 +34,9 @@ impl Action for MoveUnpackedNix {
     fn action_tag() -> ActionTag {
         ActionTag("move_unpacked_nix")
     }
+    fn paths_written(&self) -> Vec<PathBuf> {
+        vec![self.unpacked_path.clone(), Path::new(DEST).join("store")]
+    }
     fn tracing_synopsis(&self) -> String {
         "Move the downloaded Nix into `/nix`".to_string()
     }
@@ -158,7 +161,7 @@ impl Action for MoveUnpackedNix {
 }
 
 #[non_exhaustive]
-#[derive(Debug, thiserror::Error)]
+#[derive(Debug, thiserror::Error, strum::IntoStaticStr)]
 pub enum MoveUnpackedNixError {
     #[error("Glob pattern error")]
     GlobPatternError(
@@ -3,7 +3,7 @@ use std::path::{Path, PathBuf};
 use tokio::fs::remove_dir_all;
 use tracing::{span, Span};
 
-use crate::action::{Action, ActionDescription, ActionErrorKind, ActionState};
+use crate::action::{Action, ActionDescription, ActionErrorKind};
 use crate::action::{ActionError, StatefulAction};
 
 /** Remove a directory, does nothing on revert.
@@ -18,10 +18,7 @@ impl RemoveDirectory {
     pub async fn plan(path: impl AsRef<Path>) -> Result<StatefulAction<Self>, ActionError> {
         let path = path.as_ref().to_path_buf();
 
-        Ok(StatefulAction {
-            action: Self { path },
-            state: ActionState::Uncompleted,
-        })
+        Ok(StatefulAction::uncompleted(Self { path }))
     }
 }
 
@@ -31,6 +28,9 @@ impl Action for RemoveDirectory {
     fn action_tag() -> crate::action::ActionTag {
         crate::action::ActionTag("remove_directory")
     }
+    fn paths_written(&self) -> Vec<PathBuf> {
+        vec![self.path.clone()]
+    }
     fn tracing_synopsis(&self) -> String {
         format!("Remove directory `{}`", self.path.display())
     }
@@ -0,0 +1,210 @@
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use nix::unistd::{Gid, Uid};
+use tokio::fs::{read_to_string, remove_file};
+use tracing::{span, Span};
+
+use super::create_file::{remove_temp_file, temp_file_path, write_temp_file, PriorFile};
+use crate::action::{
+    Action, ActionDescription, ActionError, ActionErrorKind, ActionTag, StatefulAction,
+};
+
+/** Remove a file, the inverse of [`CreateFile`](super::CreateFile)
+
+If `restore` is set the file (and its mode and ownership) is snapshotted on execute, and put back on revert.
+ */
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone)]
+pub struct RemoveFile {
+    path: PathBuf,
+    restore: bool,
+    snapshot: Option<PriorFile>,
+}
+
+impl RemoveFile {
+    #[tracing::instrument(level = "debug", skip_all)]
+    pub async fn plan(
+        path: impl AsRef<Path>,
+        restore: bool,
+    ) -> Result<StatefulAction<Self>, ActionError> {
+        let path = path.as_ref().to_path_buf();
+        let this = Self {
+            path,
+            restore,
+            snapshot: None,
+        };
+
+        if !this.path.exists() {
+            tracing::debug!("Removing file `{}` already complete", this.path.display());
+            return Ok(StatefulAction::completed(this));
+        }
+        if !this.path.is_file() {
+            return Err(Self::error(ActionErrorKind::PathWasNotFile(this.path)));
+        }
+
+        Ok(StatefulAction::uncompleted(this))
+    }
+}
+
+#[async_trait::async_trait]
+#[typetag::serde(name = "remove_file")]
+impl Action for RemoveFile {
+    fn action_tag() -> ActionTag {
+        ActionTag("remove_file")
+    }
+    fn paths_written(&self) -> Vec<PathBuf> {
+        vec![self.path.clone()]
+    }
+    fn tracing_synopsis(&self) -> String {
+        format!("Remove file `{}`", self.path.display())
+    }
+
+    fn tracing_span(&self) -> Span {
+        span!(
+            tracing::Level::DEBUG,
+            "remove_file",
+            path = tracing::field::display(self.path.display()),
+            restore = self.restore,
+        )
+    }
+
+    fn execute_description(&self) -> Vec<ActionDescription> {
+        let mut explanation = vec![];
+        if self.restore {
+            explanation.push("A copy is kept, so it can be restored on uninstall".to_string());
+        }
+        vec![ActionDescription::new(self.tracing_synopsis(), explanation)]
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn execute(&mut self) -> Result<(), ActionError> {
+        let Self {
+            path,
+            restore,
+            snapshot,
+        } = self;
+
+        // The user may have already removed it
+        if !path.exists() {
+            tracing::debug!("File `{}` not present, skipping", path.display());
+            return Ok(());
+        }
+
+        if *restore && snapshot.is_none() {
+            let metadata = path
+                .metadata()
+                .map_err(|e| Self::error(ActionErrorKind::GettingMetadata(path.clone(), e)))?;
+            let buf = read_to_string(&path)
+                .await
+                .map_err(|e| Self::error(ActionErrorKind::Read(path.clone(), e)))?;
+            *snapshot = Some(PriorFile {
+                buf,
+                mode: metadata.permissions().mode() & 0o7777,
+                uid: metadata.uid(),
+                gid: metadata.gid(),
+            });
+        }
+
+        remove_file(&path)
+            .await
+            .map_err(|e| Self::error(ActionErrorKind::Remove(path.clone(), e)))?;
+
+        Ok(())
+    }
+
+    fn revert_description(&self) -> Vec<ActionDescription> {
+        match &self.snapshot {
+            Some(snapshot) => vec![ActionDescription::new(
+                format!("Restore file `{}`", self.path.display()),
+                vec![format!(
+                    "Writes back the {} bytes it held before it was removed",
+                    snapshot.buf.len()
+                )],
+            )],
+            None => vec![],
+        }
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn revert(&mut self) -> Result<(), ActionError> {
+        let Some(snapshot) = &self.snapshot else {
+            return Ok(());
+        };
+        if self.path.exists() {
+            return Err(Self::error(RemoveFileError::RestoreWouldOverwrite {
+                path: self.path.clone(),
+            }));
+        }
+
+        let temp_file_path = temp_file_path(&self.path);
+        if let Err(err) = write_temp_file(
+            &temp_file_path,
+            &self.path,
+            &snapshot.buf,
+            Some(Uid::from_raw(snapshot.uid)),
+            Some(Gid::from_raw(snapshot.gid)),
+            Some(snapshot.mode),
+        )
+        .await
+        {
+            remove_temp_file(&temp_file_path).await;
+            return Err(Self::error(err));
+        }
+        self.snapshot = None;
+
+        Ok(())
+    }
+}
+
+#[non_exhaustive]
+#[derive(Debug, thiserror::Error, strum::IntoStaticStr)]
+pub enum RemoveFileError {
+    #[error("Restoring `{}` would overwrite the file which now exists there", .path.display())]
+    RestoreWouldOverwrite { path: PathBuf },
+}
+
+impl From<RemoveFileError> for ActionErrorKind {
+    fn from(val: RemoveFileError) -> Self {
+        ActionErrorKind::Custom(Box::new(val))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tokio::fs::write;
+
+    #[tokio::test]
+    async fn removes_and_restores_file() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let test_file = temp_dir.path().join("removes_and_restores_file");
+        write(&test_file, "Test").await?;
+        tokio::fs::set_permissions(&test_file, PermissionsExt::from_mode(0o600)).await?;
+
+        let mut action = RemoveFile::plan(&test_file, true).await?;
+        action.try_execute().await?;
+        assert!(!test_file.exists(), "File should be removed");
+
+        action.try_revert().await?;
+        assert_eq!(read_to_string(&test_file).await?, "Test");
+        assert_eq!(test_file.metadata()?.permissions().mode() & 0o7777, 0o600);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn refuses_to_overwrite_on_restore() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let test_file = temp_dir.path().join("refuses_to_overwrite_on_restore");
+        write(&test_file, "Test").await?;
+
+        let mut action = RemoveFile::plan(&test_file, true).await?;
+        action.try_execute().await?;
+        write(&test_file, "Recreated").await?;
+
+        assert!(action.try_revert().await.is_err());
+        assert_eq!(read_to_string(&test_file).await?, "Recreated");
+
+        Ok(())
+    }
+}
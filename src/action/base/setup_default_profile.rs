@@ -31,6 +31,9 @@ impl Action for SetupDefaultProfile {
     fn action_tag() -> ActionTag {
         ActionTag("setup_default_profile")
     }
+    fn paths_read(&self) -> Vec<PathBuf> {
+        vec![self.unpacked_path.clone()]
+    }
     fn tracing_synopsis(&self) -> String {
         "Setup the default Nix profile".to_string()
     }
@@ -177,7 +180,7 @@ impl Action for SetupDefaultProfile {
 }
 
 #[non_exhaustive]
-#[derive(Debug, thiserror::Error)]
+#[derive(Debug, thiserror::Error, strum::IntoStaticStr)]
 pub enum SetupDefaultProfileError {
     #[error("No root home found to place channel configuration in")]
     NoRootHome,
@@ -36,6 +36,9 @@ impl Action for ConfigureEnterpriseEditionInitService {
     fn action_tag() -> ActionTag {
         ActionTag("configure_enterprise_edition_init_service")
     }
+    fn category(&self) -> crate::action::ActionCategory {
+        crate::action::ActionCategory::Service
+    }
     fn tracing_synopsis(&self) -> String {
         "Configure the Determinate Nix Enterprise Edition daemon related settings with launchctl"
             .to_string()
@@ -119,6 +119,9 @@ impl Action for ConfigureInitService {
     fn action_tag() -> ActionTag {
         ActionTag("configure_init_service")
     }
+    fn category(&self) -> crate::action::ActionCategory {
+        crate::action::ActionCategory::Service
+    }
     fn tracing_synopsis(&self) -> String {
         match self.init {
             #[cfg(target_os = "linux")]
@@ -521,7 +524,7 @@ impl Action for ConfigureInitService {
 }
 
 #[non_exhaustive]
-#[derive(Debug, thiserror::Error)]
+#[derive(Debug, thiserror::Error, strum::IntoStaticStr)]
 pub enum ConfigureNixDaemonServiceError {
     #[error("No supported init system found")]
     InitNotSupported,
@@ -1,4 +1,7 @@
-use std::path::{Path, PathBuf};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use crate::{
     action::{
@@ -46,6 +49,8 @@ impl ConfigureNix {
             settings.nix_build_group_name.clone(),
             settings.proxy.clone(),
             settings.ssl_cert_file.clone(),
+            settings.http_timeout.map(Duration::from_secs),
+            None,
             settings.extra_conf.clone(),
             settings.force,
         )
@@ -126,6 +131,20 @@ impl Action for ConfigureNix {
     fn action_tag() -> ActionTag {
         ActionTag("configure_nix")
     }
+    fn category(&self) -> crate::action::ActionCategory {
+        crate::action::ActionCategory::Configuration
+    }
+    fn paths_written(&self) -> Vec<PathBuf> {
+        let mut paths = self.setup_default_profile.paths_written();
+        if let Some(configure_shell_profile) = &self.configure_shell_profile {
+            paths.extend(configure_shell_profile.paths_written());
+        }
+        paths.extend(self.place_nix_configuration.paths_written());
+        paths
+    }
+    fn paths_read(&self) -> Vec<PathBuf> {
+        self.setup_default_profile.paths_read()
+    }
     fn tracing_synopsis(&self) -> String {
         "Configure Nix".to_string()
     }
@@ -210,6 +229,15 @@ impl Action for ConfigureNix {
         Ok(())
     }
 
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn dry_run(&self) {
+        self.setup_default_profile.try_dry_run().await;
+        self.place_nix_configuration.try_dry_run().await;
+        if let Some(configure_shell_profile) = &self.configure_shell_profile {
+            configure_shell_profile.try_dry_run().await;
+        }
+    }
+
     fn revert_description(&self) -> Vec<ActionDescription> {
         let Self {
             setup_default_profile,
@@ -256,7 +284,7 @@ impl Action for ConfigureNix {
 }
 
 #[non_exhaustive]
-#[derive(Debug, thiserror::Error)]
+#[derive(Debug, thiserror::Error, strum::IntoStaticStr)]
 pub enum ConfigureNixError {
     #[error("Unarchived Nix store did not appear to include a `nss-cacert` location")]
     NoNssCacert,
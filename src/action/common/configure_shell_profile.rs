@@ -1,12 +1,17 @@
-use crate::action::base::{create_or_insert_into_file, CreateDirectory, CreateOrInsertIntoFile};
+use crate::action::base::{
+    create_or_insert_into_file, BackupShellProfile, CreateDirectory, CreateOrInsertIntoFile,
+};
 use crate::action::{
-    Action, ActionDescription, ActionError, ActionErrorKind, ActionTag, StatefulAction,
+    default_max_concurrency, Action, ActionDescription, ActionError, ActionErrorKind, ActionTag,
+    StatefulAction,
 };
 use crate::planner::ShellProfileLocations;
+use crate::self_test::Shell;
 
 use nix::unistd::User;
 use std::path::{Path, PathBuf};
-use tokio::task::JoinSet;
+use std::sync::Arc;
+use tokio::{sync::Semaphore, task::JoinSet};
 use tracing::{span, Instrument, Span};
 
 const PROFILE_NIX_FILE_SHELL: &str = "/nix/var/nix/profiles/default/etc/profile.d/nix-daemon.sh";
@@ -14,12 +19,19 @@ const PROFILE_NIX_FILE_FISH: &str = "/nix/var/nix/profiles/default/etc/profile.d
 
 /**
 Configure any detected shell profiles to include Nix support
+
+The existing profiles are backed up with [`BackupShellProfile`] first, and restored on revert unless they were edited since.
  */
 #[derive(Debug, serde::Deserialize, serde::Serialize, Clone)]
 pub struct ConfigureShellProfile {
     locations: ShellProfileLocations,
+    #[serde(default)]
+    backup_shell_profiles: Vec<StatefulAction<BackupShellProfile>>,
     create_directories: Vec<StatefulAction<CreateDirectory>>,
     create_or_insert_into_files: Vec<StatefulAction<CreateOrInsertIntoFile>>,
+    /// How many profiles are edited at once, one per CPU
+    #[serde(default = "default_max_concurrency")]
+    max_concurrency: usize,
 }
 
 impl ConfigureShellProfile {
@@ -29,6 +41,15 @@ impl ConfigureShellProfile {
     ) -> Result<StatefulAction<Self>, ActionError> {
         let mut create_or_insert_files = Vec::default();
         let mut create_directories = Vec::default();
+        let mut backup_shell_profiles = Vec::default();
+
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+            backup_shell_profiles.push(
+                BackupShellProfile::plan(shell, &locations)
+                    .await
+                    .map_err(Self::error)?,
+            );
+        }
 
         let shell_buf = format!(
             "\n\
@@ -173,8 +194,10 @@ impl ConfigureShellProfile {
 
         Ok(Self {
             locations,
+            backup_shell_profiles,
             create_directories,
             create_or_insert_into_files: create_or_insert_files,
+            max_concurrency: default_max_concurrency(),
         }
         .into())
     }
@@ -186,6 +209,27 @@ impl Action for ConfigureShellProfile {
     fn action_tag() -> ActionTag {
         ActionTag("configure_shell_profile")
     }
+    fn category(&self) -> crate::action::ActionCategory {
+        crate::action::ActionCategory::Configuration
+    }
+    fn paths_written(&self) -> Vec<PathBuf> {
+        self.backup_shell_profiles
+            .iter()
+            .flat_map(|backup_shell_profile| backup_shell_profile.paths_written())
+            .chain(
+                self.create_directories
+                    .iter()
+                    .flat_map(|create_directory| create_directory.paths_written()),
+            )
+            .chain(
+                self.create_or_insert_into_files
+                    .iter()
+                    .flat_map(|create_or_insert_into_file| {
+                        create_or_insert_into_file.paths_written()
+                    }),
+            )
+            .collect()
+    }
     fn tracing_synopsis(&self) -> String {
         "Configure the shell profiles".to_string()
     }
@@ -203,19 +247,28 @@ impl Action for ConfigureShellProfile {
 
     #[tracing::instrument(level = "debug", skip_all)]
     async fn execute(&mut self) -> Result<(), ActionError> {
+        for backup_shell_profile in &mut self.backup_shell_profiles {
+            backup_shell_profile.try_execute().await?;
+        }
         for create_directory in &mut self.create_directories {
             create_directory.try_execute().await?;
         }
 
         let mut set = JoinSet::new();
         let mut errors = vec![];
+        let semaphore = Arc::new(Semaphore::new(self.max_concurrency.max(1)));
 
         for (idx, create_or_insert_into_file) in
             self.create_or_insert_into_files.iter_mut().enumerate()
         {
             let span = tracing::Span::current().clone();
             let mut create_or_insert_into_file_clone = create_or_insert_into_file.clone();
+            let semaphore = semaphore.clone();
             let _abort_handle = set.spawn(async move {
+                let _permit = semaphore
+                    .acquire_owned()
+                    .await
+                    .expect("Semaphore is never closed");
                 create_or_insert_into_file_clone
                     .try_execute()
                     .instrument(span)
@@ -248,10 +301,48 @@ impl Action for ConfigureShellProfile {
         Ok(())
     }
 
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn dry_run(&self) {
+        for backup_shell_profile in &self.backup_shell_profiles {
+            backup_shell_profile.try_dry_run().await;
+        }
+        for create_directory in &self.create_directories {
+            create_directory.try_dry_run().await;
+        }
+        for create_or_insert_into_file in &self.create_or_insert_into_files {
+            create_or_insert_into_file.try_dry_run().await;
+        }
+    }
+
     fn revert_description(&self) -> Vec<ActionDescription> {
+        let mut explanation = vec!["Update shell profiles to no longer import Nix".to_string()];
+        for create_or_insert_into_file in &self.create_or_insert_into_files {
+            explanation.extend(
+                create_or_insert_into_file
+                    .describe_revert()
+                    .into_iter()
+                    .map(|description| description.description),
+            );
+        }
+        for create_directory in &self.create_directories {
+            explanation.extend(
+                create_directory
+                    .describe_revert()
+                    .into_iter()
+                    .map(|description| description.description),
+            );
+        }
+        for backup_shell_profile in &self.backup_shell_profiles {
+            explanation.extend(
+                backup_shell_profile
+                    .describe_revert()
+                    .into_iter()
+                    .map(|description| description.description),
+            );
+        }
         vec![ActionDescription::new(
             "Unconfigure the shell profiles".to_string(),
-            vec!["Update shell profiles to no longer import Nix".to_string()],
+            explanation,
         )]
     }
 
@@ -259,12 +350,18 @@ impl Action for ConfigureShellProfile {
     async fn revert(&mut self) -> Result<(), ActionError> {
         let mut set = JoinSet::new();
         let mut errors = vec![];
+        let semaphore = Arc::new(Semaphore::new(self.max_concurrency.max(1)));
 
         for (idx, create_or_insert_into_file) in
             self.create_or_insert_into_files.iter_mut().enumerate()
         {
             let mut create_or_insert_file_clone = create_or_insert_into_file.clone();
+            let semaphore = semaphore.clone();
             let _abort_handle = set.spawn(async move {
+                let _permit = semaphore
+                    .acquire_owned()
+                    .await
+                    .expect("Semaphore is never closed");
                 create_or_insert_file_clone.try_revert().await?;
                 Result::<_, _>::Ok((idx, create_or_insert_file_clone))
             });
@@ -287,6 +384,12 @@ impl Action for ConfigureShellProfile {
             }
         }
 
+        for backup_shell_profile in self.backup_shell_profiles.iter_mut() {
+            if let Err(err) = backup_shell_profile.try_revert().await {
+                errors.push(err);
+            }
+        }
+
         if errors.is_empty() {
             Ok(())
         } else if errors.len() == 1 {
@@ -52,10 +52,26 @@ impl Action for CreateNixTree {
     fn action_tag() -> ActionTag {
         ActionTag("create_nix_tree")
     }
+    fn category(&self) -> crate::action::ActionCategory {
+        crate::action::ActionCategory::Prepare
+    }
+    fn paths_written(&self) -> Vec<std::path::PathBuf> {
+        self.create_directories
+            .iter()
+            .flat_map(|create_directory| create_directory.paths_written())
+            .collect()
+    }
     fn tracing_synopsis(&self) -> String {
         "Create a directory tree in `/nix`".to_string()
     }
 
+    fn estimated_duration(&self) -> Option<std::time::Duration> {
+        self.create_directories
+            .iter()
+            .filter_map(|create_directory| create_directory.estimated_duration())
+            .reduce(|total, estimate| total + estimate)
+    }
+
     fn tracing_span(&self) -> Span {
         span!(tracing::Level::DEBUG, "create_nix_tree",)
     }
@@ -85,6 +101,13 @@ impl Action for CreateNixTree {
         Ok(())
     }
 
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn dry_run(&self) {
+        for create_directory in &self.create_directories {
+            create_directory.try_dry_run().await;
+        }
+    }
+
     fn revert_description(&self) -> Vec<ActionDescription> {
         vec![ActionDescription::new(
             "Remove the directory tree in `/nix`".to_string(),
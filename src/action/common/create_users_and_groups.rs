@@ -71,6 +71,9 @@ impl Action for CreateUsersAndGroups {
     fn action_tag() -> ActionTag {
         ActionTag("create_users_and_group")
     }
+    fn category(&self) -> crate::action::ActionCategory {
+        crate::action::ActionCategory::Users
+    }
     fn tracing_synopsis(&self) -> String {
         if self.create_users.is_empty() {
             format!("Create build group (GID {})", self.nix_build_group_id)
@@ -10,6 +10,7 @@ pub(crate) mod create_users_and_groups;
 pub(crate) mod delete_users;
 pub(crate) mod place_nix_configuration;
 pub(crate) mod provision_nix;
+pub(crate) mod update_channels;
 
 #[cfg(target_os = "macos")]
 pub use configure_enterprise_edition_init_service::ConfigureEnterpriseEditionInitService;
@@ -21,3 +22,4 @@ pub use create_users_and_groups::CreateUsersAndGroups;
 pub use delete_users::DeleteUsersInGroup;
 pub use place_nix_configuration::PlaceNixConfiguration;
 pub use provision_nix::ProvisionNix;
+pub use update_channels::{UpdateChannels, UpdateChannelsError};
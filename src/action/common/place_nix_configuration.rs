@@ -6,10 +6,10 @@ use crate::action::base::{CreateDirectory, CreateOrMergeNixConfig};
 use crate::action::{
     Action, ActionDescription, ActionError, ActionErrorKind, ActionTag, StatefulAction,
 };
-use crate::parse_ssl_cert;
+use crate::http_client;
 use crate::settings::UrlOrPathOrString;
 use indexmap::map::Entry;
-use std::path::PathBuf;
+use std::{path::PathBuf, time::Duration};
 
 const NIX_CONF_FOLDER: &str = "/etc/nix";
 const NIX_CONF: &str = "/etc/nix/nix.conf";
@@ -29,31 +29,23 @@ impl PlaceNixConfiguration {
         nix_build_group_name: String,
         proxy: Option<Url>,
         ssl_cert_file: Option<PathBuf>,
+        timeout: Option<Duration>,
+        client: impl Into<Option<reqwest::Client>>,
         extra_conf: Vec<UrlOrPathOrString>,
         force: bool,
     ) -> Result<StatefulAction<Self>, ActionError> {
+        let client = client.into();
         let mut extra_conf_text = vec![];
         for extra in extra_conf {
             let buf = match &extra {
                 UrlOrPathOrString::Url(url) => match url.scheme() {
                     "https" | "http" => {
-                        let mut buildable_client = reqwest::Client::builder();
-                        if let Some(proxy) = &proxy {
-                            buildable_client = buildable_client.proxy(
-                                reqwest::Proxy::all(proxy.clone())
-                                    .map_err(ActionErrorKind::Reqwest)
-                                    .map_err(Self::error)?,
-                            )
-                        }
-                        if let Some(ssl_cert_file) = &ssl_cert_file {
-                            let ssl_cert =
-                                parse_ssl_cert(ssl_cert_file).await.map_err(Self::error)?;
-                            buildable_client = buildable_client.add_root_certificate(ssl_cert);
-                        }
-                        let client = buildable_client
-                            .build()
-                            .map_err(ActionErrorKind::Reqwest)
-                            .map_err(Self::error)?;
+                        let client = match &client {
+                            Some(client) => client.clone(),
+                            None => http_client(proxy.as_ref(), ssl_cert_file.as_deref(), timeout)
+                                .await
+                                .map_err(Self::error)?,
+                        };
                         let req = client
                             .get(url.clone())
                             .build()
@@ -154,6 +146,16 @@ impl Action for PlaceNixConfiguration {
     fn action_tag() -> ActionTag {
         ActionTag("place_nix_configuration")
     }
+    fn category(&self) -> crate::action::ActionCategory {
+        crate::action::ActionCategory::Configuration
+    }
+    fn paths_written(&self) -> Vec<PathBuf> {
+        [
+            self.create_directory.paths_written(),
+            self.create_or_merge_nix_config.paths_written(),
+        ]
+        .concat()
+    }
     fn tracing_synopsis(&self) -> String {
         format!("Place the Nix configuration in `{NIX_CONF}`")
     }
@@ -197,13 +199,27 @@ impl Action for PlaceNixConfiguration {
         Ok(())
     }
 
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn dry_run(&self) {
+        self.create_directory.try_dry_run().await;
+        self.create_or_merge_nix_config.try_dry_run().await;
+    }
+
     fn revert_description(&self) -> Vec<ActionDescription> {
+        let mut explanation = vec![
+            "This file is read by the Nix daemon to set its configuration options at runtime."
+                .to_string(),
+        ];
+        explanation.extend(
+            self.create_or_merge_nix_config
+                .describe_revert()
+                .into_iter()
+                .chain(self.create_directory.describe_revert())
+                .map(|description| description.description),
+        );
         vec![ActionDescription::new(
             format!("Remove the Nix configuration in `{NIX_CONF}`"),
-            vec![
-                "This file is read by the Nix daemon to set its configuration options at runtime."
-                    .to_string(),
-            ],
+            explanation,
         )]
     }
 
@@ -8,7 +8,7 @@ use crate::{
     },
     settings::{CommonSettings, SCRATCH_DIR},
 };
-use std::path::PathBuf;
+use std::{path::PathBuf, time::Duration};
 
 /**
 Place Nix and it's requirements onto the target
@@ -28,6 +28,8 @@ impl ProvisionNix {
             PathBuf::from(SCRATCH_DIR),
             settings.proxy.clone(),
             settings.ssl_cert_file.clone(),
+            settings.http_timeout.map(Duration::from_secs),
+            None,
         )
         .await?;
 
@@ -50,10 +52,35 @@ impl Action for ProvisionNix {
     fn action_tag() -> ActionTag {
         ActionTag("provision_nix")
     }
+    fn category(&self) -> crate::action::ActionCategory {
+        crate::action::ActionCategory::Provision
+    }
+    fn paths_written(&self) -> Vec<PathBuf> {
+        [
+            self.fetch_nix.paths_written(),
+            self.create_nix_tree.paths_written(),
+            self.move_unpacked_nix.paths_written(),
+        ]
+        .concat()
+    }
+    fn paths_read(&self) -> Vec<PathBuf> {
+        self.fetch_nix.paths_read()
+    }
     fn tracing_synopsis(&self) -> String {
         "Provision Nix".to_string()
     }
 
+    fn estimated_duration(&self) -> Option<Duration> {
+        [
+            self.fetch_nix.estimated_duration(),
+            self.create_nix_tree.estimated_duration(),
+            self.move_unpacked_nix.estimated_duration(),
+        ]
+        .into_iter()
+        .flatten()
+        .reduce(|total, estimate| total + estimate)
+    }
+
     fn tracing_span(&self) -> Span {
         span!(tracing::Level::DEBUG, "provision_nix",)
     }
@@ -100,6 +127,13 @@ impl Action for ProvisionNix {
         Ok(())
     }
 
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn dry_run(&self) {
+        self.fetch_nix.try_dry_run().await;
+        self.create_nix_tree.try_dry_run().await;
+        self.move_unpacked_nix.try_dry_run().await;
+    }
+
     fn revert_description(&self) -> Vec<ActionDescription> {
         let Self {
             fetch_nix,
@@ -119,7 +153,8 @@ impl Action for ProvisionNix {
     async fn revert(&mut self) -> Result<(), ActionError> {
         let mut errors = vec![];
 
-        if let Err(err) = self.fetch_nix.try_revert().await {
+        // Revert in the reverse of the order they were executed in
+        if let Err(err) = self.move_unpacked_nix.try_revert().await {
             errors.push(err)
         }
 
@@ -127,7 +162,7 @@ impl Action for ProvisionNix {
             errors.push(err)
         }
 
-        if let Err(err) = self.move_unpacked_nix.try_revert().await {
+        if let Err(err) = self.fetch_nix.try_revert().await {
             errors.push(err)
         }
 
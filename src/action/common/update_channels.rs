@@ -0,0 +1,229 @@
+use std::process::Output;
+
+use nix::unistd::{Uid, User};
+use tokio::process::Command;
+use tracing::{span, Span};
+
+use crate::action::{
+    Action, ActionDescription, ActionError, ActionErrorKind, ActionTag, StatefulAction,
+};
+
+const NIX_CHANNEL_BIN: &str = "/nix/var/nix/profiles/default/bin/nix-channel";
+
+/**
+Run `nix-channel --update`, optionally as a given user, so that channels listed in their
+`.nix-channels` are fetched and usable
+
+Planned as skipped if the user has no `.nix-channels`, as there would be nothing to fetch.
+No built-in planner schedules it, it is meant for planners which place a `.nix-channels` of their own.
+ */
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone)]
+pub struct UpdateChannels {
+    user: Option<String>,
+}
+
+impl UpdateChannels {
+    #[tracing::instrument(level = "debug", skip_all)]
+    pub async fn plan(
+        user: impl Into<Option<String>>,
+    ) -> Result<StatefulAction<Self>, ActionError> {
+        let this = Self { user: user.into() };
+        let home = match &this.user {
+            Some(_) => this.target_user()?.map(|user| user.dir),
+            None => User::from_uid(Uid::effective())
+                .ok()
+                .flatten()
+                .map(|user| user.dir),
+        };
+        if !home.is_some_and(|home| home.join(".nix-channels").exists()) {
+            tracing::debug!("No `.nix-channels` to update");
+            return Ok(StatefulAction::skipped(this));
+        }
+        Ok(this.into())
+    }
+
+    /// The user to run as, if not the current one
+    fn target_user(&self) -> Result<Option<User>, ActionError> {
+        let Some(user) = &self.user else {
+            return Ok(None);
+        };
+        User::from_name(user)
+            .map_err(|e| ActionErrorKind::GettingUserId(user.clone(), e))
+            .map_err(Self::error)?
+            .ok_or_else(|| ActionErrorKind::NoUser(user.clone()))
+            .map_err(Self::error)
+            .map(Some)
+    }
+
+    /// The `nix-channel --update` command, with the environment of the target user
+    fn command(&self) -> Result<Command, ActionError> {
+        let mut command = Command::new(NIX_CHANNEL_BIN);
+        command.process_group(0);
+        command.arg("--update");
+        command.stdin(std::process::Stdio::null());
+        if let Some(user) = self.target_user()? {
+            command.uid(user.uid.as_raw());
+            command.gid(user.gid.as_raw());
+            command.env("HOME", &user.dir);
+            command.env("USER", &user.name);
+        }
+        Ok(command)
+    }
+}
+
+#[async_trait::async_trait]
+#[typetag::serde(name = "update_channels")]
+impl Action for UpdateChannels {
+    fn action_tag() -> ActionTag {
+        ActionTag("update_channels")
+    }
+    fn max_retries(&self) -> u32 {
+        // Fetching the channels again over a failed attempt is harmless
+        3
+    }
+    fn redact_diagnostics() -> bool {
+        // Runs against the channels in a user's home directory
+        true
+    }
+    fn tracing_synopsis(&self) -> String {
+        match &self.user {
+            Some(user) => format!("Update the Nix channels of user `{user}`"),
+            None => "Update the Nix channels".to_string(),
+        }
+    }
+
+    fn tracing_span(&self) -> Span {
+        span!(tracing::Level::DEBUG, "update_channels", user = self.user,)
+    }
+
+    fn execute_description(&self) -> Vec<ActionDescription> {
+        vec![ActionDescription::new(
+            self.tracing_synopsis(),
+            vec![format!(
+                "Runs `{NIX_CHANNEL_BIN} --update` to fetch the channels listed in `.nix-channels`"
+            )],
+        )]
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn execute(&mut self) -> Result<(), ActionError> {
+        let mut command = self.command()?;
+        tracing::trace!("Executing `{:?}`", command.as_std());
+        let output = command
+            .output()
+            .await
+            .map_err(|e| ActionErrorKind::command(&command, e))
+            .map_err(Self::error)?;
+        if !output.status.success() {
+            return Err(Self::error(UpdateChannelsError::Update { output }));
+        }
+
+        Ok(())
+    }
+
+    fn revert_description(&self) -> Vec<ActionDescription> {
+        vec![/* Deliberately empty -- this is a noop */]
+    }
+
+    #[tracing::instrument(level = "debug", skip_all)]
+    async fn revert(&mut self) -> Result<(), ActionError> {
+        // The fetched channels live in the Nix store and are removed along with it
+        Ok(())
+    }
+}
+
+#[non_exhaustive]
+#[derive(Debug, thiserror::Error, strum::IntoStaticStr)]
+pub enum UpdateChannelsError {
+    #[error("`nix-channel --update` failed with {}, stderr:\n{}", .output.status, String::from_utf8_lossy(&.output.stderr))]
+    Update { output: Output },
+}
+
+/// Fragments of the `stderr` of `nix-channel --update` which point at a network failure
+const TRANSIENT_STDERR: &[&str] = &[
+    "unable to download",
+    "Could not resolve host",
+    "Couldn't resolve host",
+    "Connection timed out",
+    "Connection refused",
+    "Timeout was reached",
+    "HTTP error 5",
+];
+
+impl UpdateChannelsError {
+    /// Whether retrying could succeed, such as if fetching a channel failed or `nix-channel` was killed
+    ///
+    /// A channel which does not exist, or does not evaluate, fails the same way on every attempt.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Update { output } => {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                output.status.code().is_none()
+                    || TRANSIENT_STDERR
+                        .iter()
+                        .any(|fragment| stderr.contains(fragment))
+            },
+        }
+    }
+}
+
+impl From<UpdateChannelsError> for ActionErrorKind {
+    fn from(val: UpdateChannelsError) -> Self {
+        ActionErrorKind::Custom(Box::new(val))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn updates_as_the_target_user() -> eyre::Result<()> {
+        let current = User::from_uid(Uid::effective())?.ok_or_else(|| eyre::eyre!("No user"))?;
+        let action = UpdateChannels {
+            user: Some(current.name.clone()),
+        };
+        let command = action.command()?;
+        let command = command.as_std();
+        assert_eq!(command.get_program(), NIX_CHANNEL_BIN);
+        assert_eq!(command.get_args().collect::<Vec<_>>(), ["--update"]);
+        let envs = command.get_envs().collect::<Vec<_>>();
+        assert!(envs.contains(&("HOME".as_ref(), Some(current.dir.as_os_str()))));
+        assert!(envs.contains(&("USER".as_ref(), Some(current.name.as_ref()))));
+
+        let action = UpdateChannels { user: None };
+        assert_eq!(action.command()?.as_std().get_envs().count(), 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reports_failed_update_output() -> eyre::Result<()> {
+        let output = Command::new("sh")
+            .args(["-c", "echo 'unable to download' >&2; exit 1"])
+            .output()
+            .await?;
+        let err = ActionErrorKind::from(UpdateChannelsError::Update { output });
+        assert!(err.to_string().contains("unable to download"), "{err}");
+        assert!(err.is_transient());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn classifies_failed_updates() -> eyre::Result<()> {
+        let broken = Command::new("sh")
+            .args([
+                "-c",
+                "echo 'error: syntax error, unexpected end of file' >&2; exit 1",
+            ])
+            .output()
+            .await?;
+        assert!(!UpdateChannelsError::Update { output: broken }.is_transient());
+
+        let killed = Command::new("sh")
+            .args(["-c", "kill -9 $$"])
+            .output()
+            .await?;
+        assert!(UpdateChannelsError::Update { output: killed }.is_transient());
+        Ok(())
+    }
+}
@@ -41,6 +41,9 @@ impl Action for EnsureSteamosNixDirectory {
     fn action_tag() -> ActionTag {
         ActionTag("ensure_steamos_nix_directory")
     }
+    fn category(&self) -> crate::action::ActionCategory {
+        crate::action::ActionCategory::Prepare
+    }
     fn tracing_synopsis(&self) -> String {
         "Ensure SteamOS's `/nix` directory exists".to_string()
     }
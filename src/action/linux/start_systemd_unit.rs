@@ -1,7 +1,9 @@
 use tokio::process::Command;
 use tracing::{span, Span};
 
-use crate::action::{ActionError, ActionErrorKind, ActionState, ActionTag, StatefulAction};
+use crate::action::{
+    stateful::new_action_id, ActionError, ActionErrorKind, ActionState, ActionTag, StatefulAction,
+};
 use crate::execute_command;
 
 use crate::action::{Action, ActionDescription};
@@ -43,6 +45,8 @@ impl StartSystemdUnit {
                 enable,
             },
             state,
+            id: new_action_id(),
+            elapsed: None,
         })
     }
 }
@@ -53,6 +57,9 @@ impl Action for StartSystemdUnit {
     fn action_tag() -> ActionTag {
         ActionTag("start_systemd_unit")
     }
+    fn category(&self) -> crate::action::ActionCategory {
+        crate::action::ActionCategory::Service
+    }
     fn tracing_synopsis(&self) -> String {
         format!("Enable (and start) the systemd unit `{}`", self.unit)
     }
@@ -158,7 +165,7 @@ impl Action for StartSystemdUnit {
 }
 
 #[non_exhaustive]
-#[derive(Debug, thiserror::Error)]
+#[derive(Debug, thiserror::Error, strum::IntoStaticStr)]
 pub enum StartSystemdUnitError {
     #[error("Failed to execute command")]
     Command(#[source] std::io::Error),
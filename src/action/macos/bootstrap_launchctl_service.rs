@@ -119,7 +119,7 @@ impl Action for BootstrapLaunchctlService {
                 Command::new("launchctl")
                     .process_group(0)
                     .arg("enable")
-                    .arg(&format!("{domain}/{service}"))
+                    .arg(format!("{domain}/{service}"))
                     .stdin(std::process::Stdio::null()),
             )
             .await
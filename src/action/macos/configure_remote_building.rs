@@ -61,6 +61,9 @@ impl Action for ConfigureRemoteBuilding {
     fn action_tag() -> ActionTag {
         ActionTag("configure_remote_building")
     }
+    fn category(&self) -> crate::action::ActionCategory {
+        crate::action::ActionCategory::Configuration
+    }
     fn tracing_synopsis(&self) -> String {
         "Configuring zsh to support using Nix in non-interactive shells".to_string()
     }
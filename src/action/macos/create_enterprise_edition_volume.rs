@@ -89,6 +89,9 @@ impl Action for CreateEnterpriseEditionVolume {
     fn action_tag() -> ActionTag {
         ActionTag("create_enterprise_edition_volume")
     }
+    fn category(&self) -> crate::action::ActionCategory {
+        crate::action::ActionCategory::Prepare
+    }
     fn tracing_synopsis(&self) -> String {
         format!(
             "Create an encrypted APFS volume `{name}` for Nix on `{disk}` and add it to `/etc/fstab` mounting on `/nix`",
@@ -207,7 +207,7 @@ pub struct KeepAliveOpts {
 }
 
 #[non_exhaustive]
-#[derive(Debug, thiserror::Error)]
+#[derive(Debug, thiserror::Error, strum::IntoStaticStr)]
 pub enum CreateNixHookServiceError {
     #[error(
         "`{path}` exists and contains content different than expected. Consider removing the file."
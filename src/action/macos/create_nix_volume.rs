@@ -125,6 +125,9 @@ impl Action for CreateNixVolume {
     fn action_tag() -> ActionTag {
         ActionTag("create_nix_volume")
     }
+    fn category(&self) -> crate::action::ActionCategory {
+        crate::action::ActionCategory::Prepare
+    }
     fn tracing_synopsis(&self) -> String {
         format!(
             "Create an{maybe_encrypted} APFS volume `{name}` for Nix on `{disk}` and add it to `/etc/fstab` mounting on `/nix`",
@@ -301,7 +301,7 @@ pub struct LaunchctlMountPlist {
 }
 
 #[non_exhaustive]
-#[derive(Debug, thiserror::Error)]
+#[derive(Debug, thiserror::Error, strum::IntoStaticStr)]
 pub enum CreateVolumeServiceError {
     #[error("`{path}` contents differs, planned `{expected:?}`, discovered `{discovered:?}`")]
     DifferentPlist {
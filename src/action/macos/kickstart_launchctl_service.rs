@@ -147,7 +147,7 @@ impl Action for KickstartLaunchctlService {
     }
 }
 
-#[derive(Debug, thiserror::Error)]
+#[derive(Debug, thiserror::Error, strum::IntoStaticStr)]
 pub enum KickstartLaunchctlServiceError {
     #[error("Command `{0}` failed, stderr: {}", String::from_utf8(.1.stderr.clone()).unwrap_or_else(|_e| String::from("<Non-UTF-8>")))]
     CannotStopService(String, Output),
@@ -192,13 +192,39 @@ pub mod base;
 pub mod common;
 pub mod linux;
 pub mod macos;
+mod repeated_warnings;
 mod stateful;
 
+pub(crate) use repeated_warnings::RepeatedWarnings;
+
 pub use stateful::{ActionState, StatefulAction};
 use std::{error::Error, process::Output};
 use tokio::task::JoinError;
 use tracing::Span;
 
+/// A stable, machine readable identifier of an error variant, such as `"DifferentContent"`
+///
+/// Implemented for every error enum deriving [`strum::IntoStaticStr`], so consumers can branch on the kind of error without matching on its [`Display`](std::fmt::Display) output.
+pub trait ErrorCode {
+    fn code(&self) -> &'static str;
+}
+
+impl<E> ErrorCode for E
+where
+    for<'a> &'a E: Into<&'static str>,
+{
+    fn code(&self) -> &'static str {
+        self.into()
+    }
+}
+
+/// How many child actions a group action runs at once unless told otherwise, the number of available CPUs
+pub(crate) fn default_max_concurrency() -> usize {
+    std::thread::available_parallelism()
+        .map(|count| count.get())
+        .unwrap_or(1)
+}
+
 use crate::{error::HasExpectedErrors, settings::UrlOrPathError, CertificateError};
 
 /// An action which can be reverted or completed, with an action state
@@ -214,6 +240,10 @@ pub trait Action: Send + Sync + std::fmt::Debug + dyn_clone::DynClone {
         Self: Sized;
     /// A synopsis of the action for tracing purposes
     fn tracing_synopsis(&self) -> String;
+    /// The phase of the install this action belongs to, so UIs can group progress by it
+    fn category(&self) -> ActionCategory {
+        ActionCategory::Other
+    }
     /// A tracing span suitable for the action
     ///
     /// It should be [`tracing::Level::DEBUG`] and contain the same name as the [`typetag::serde`] entry.
@@ -240,6 +270,70 @@ pub trait Action: Send + Sync + std::fmt::Debug + dyn_clone::DynClone {
     ///
     /// This is called by [`InstallPlan::install`](crate::InstallPlan::install) through [`StatefulAction::try_execute`] which handles tracing as well as if the action needs to execute based on its `action_state`.
     async fn execute(&mut self) -> Result<(), ActionError>;
+    /// Log the side effects [`execute`][Action::execute] would have (paths touched, files written, commands run), without performing them
+    ///
+    /// If this action calls sub-[`Action`]s, care should be taken to call [`try_dry_run`][StatefulAction::try_dry_run], not [`dry_run`][Action::dry_run], so that completed actions are skipped.
+    ///
+    /// This is called by [`InstallPlan::dry_run`](crate::InstallPlan::dry_run) through [`StatefulAction::try_dry_run`]. Actions which cannot meaningfully preview their side effects can keep the default, which does nothing.
+    async fn dry_run(&self) {}
+    /// How many times [`StatefulAction::try_execute`] should retry a failed [`execute`][Action::execute], with exponential backoff
+    ///
+    /// Only actions which are safe to run [`execute`][Action::execute] on repeatedly, such as those fetching over the network, should return a non-zero value.
+    fn max_retries(&self) -> u32 {
+        0
+    }
+    /// The delay before the first retry of a failed [`execute`][Action::execute], doubled on each subsequent attempt
+    fn retry_base_delay(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(1)
+    }
+    /// A rough estimate of how long [`execute`][Action::execute] takes, used by UIs to show an ETA, see [`InstallPlan::estimated_duration`](crate::InstallPlan::estimated_duration)
+    ///
+    /// This is advisory only. Actions which cannot tell can keep the default, which is `None`.
+    fn estimated_duration(&self) -> Option<std::time::Duration> {
+        None
+    }
+    /// The paths of files this action creates (or overwrites) outright, used to detect conflicting actions before an install begins
+    ///
+    /// If this action calls sub-[`Action`]s, it should include theirs. Actions which only edit part of a file, like [`CreateOrInsertIntoFile`](crate::action::base::CreateOrInsertIntoFile), should keep the default, which is empty.
+    fn created_files(&self) -> Vec<std::path::PathBuf> {
+        vec![]
+    }
+    /// The paths this action may write, create, or remove, used to audit what a plan touches, see [`InstallPlan::filesystem_footprint`](crate::InstallPlan::filesystem_footprint)
+    ///
+    /// Unlike [`created_files`][Action::created_files], this includes files which are only partly edited. If this action calls sub-[`Action`]s, it should include theirs. The default is the [`created_files`][Action::created_files].
+    fn paths_written(&self) -> Vec<std::path::PathBuf> {
+        self.created_files()
+    }
+    /// The paths this action reads without writing them, like [`paths_written`][Action::paths_written]
+    ///
+    /// The default is empty.
+    fn paths_read(&self) -> Vec<std::path::PathBuf> {
+        vec![]
+    }
+    /// The full content each file this action writes will have after [`execute`][Action::execute], used by [`plan_diff`](crate::plan_diff) to preview changes
+    ///
+    /// Actions which do not write whole files can keep the default, which is empty, and are described by their [`execute_description`][Action::execute_description] instead.
+    fn planned_contents(&self) -> Vec<(std::path::PathBuf, String)> {
+        vec![]
+    }
+    /// Check if the system is already in the state [`execute`][Action::execute] would leave it in, such as when re-running an install which already succeeded
+    ///
+    /// This is called by [`StatefulAction::try_execute`] before [`execute`][Action::execute] on actions which are still [`Uncompleted`](ActionState::Uncompleted), a resumed action in [`Progress`](ActionState::Progress) always executes. If it returns `true`, the action is marked [`ActionState::Completed`] without executing, just like an action planned when the system already matched, and is reverted on uninstall. Actions which cannot cheaply tell can keep the default, which always executes.
+    async fn already_satisfied(&self) -> Result<bool, ActionError> {
+        Ok(false)
+    }
+    /// Describe the current state of the system against what [`execute`][Action::execute] would make it, for tools reconciling or previewing changes
+    ///
+    /// Actions can keep the default, which only reports [`already_satisfied`][Action::already_satisfied].
+    async fn state_report(&self) -> Result<ActionStateReport, ActionError> {
+        Ok(ActionStateReport::new(self.already_satisfied().await?))
+    }
+    /// Check the post-conditions of a successful [`execute`][Action::execute], such as a written file having the planned mode
+    ///
+    /// This is called by [`StatefulAction::try_execute`] immediately after [`execute`][Action::execute] succeeds, so misbehaving filesystems or races are caught before later actions (or the self-test) depend on them. Actions without meaningful post-conditions can keep the default, which always succeeds.
+    async fn verify(&self) -> Result<(), ActionError> {
+        Ok(())
+    }
     /// Perform any revert steps
     ///
     /// If this action calls sub-[`Action`]s, care should be taken to call [`try_revert`][StatefulAction::try_revert], not [`revert`][Action::revert], so that [`ActionState`] is handled correctly and tracing is done.
@@ -251,17 +345,24 @@ pub trait Action: Send + Sync + std::fmt::Debug + dyn_clone::DynClone {
     where
         Self: Sized,
     {
-        StatefulAction {
-            action: self,
-            state: ActionState::Uncompleted,
-        }
+        self.into()
+    }
+
+    /// Whether the diagnostic context of errors from this action should have home directory paths scrubbed
+    ///
+    /// Actions which deal with paths or users in a home directory should return `true`, the error class is still reported.
+    fn redact_diagnostics() -> bool
+    where
+        Self: Sized,
+    {
+        false
     }
 
     fn error(kind: impl Into<ActionErrorKind>) -> ActionError
     where
         Self: Sized,
     {
-        ActionError::new(Self::action_tag(), kind)
+        ActionError::new(Self::action_tag(), kind).redact_diagnostic(Self::redact_diagnostics())
     }
 
     // They should also have an `async fn plan(args...) -> Result<StatefulAction<Self>, ActionError>;`
@@ -287,6 +388,87 @@ impl ActionDescription {
     }
 }
 
+/// The current state of the system compared to the intent of an [`Action`], see [`Action::state_report`]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ActionStateReport {
+    /// If [`already_satisfied`][Action::already_satisfied] would return `true`
+    pub satisfied: bool,
+    /// The relevant current state, such as the content of a file, if it exists and the action describes it
+    pub current: Option<String>,
+    /// The state the action would leave the system in, if the action describes it
+    pub desired: Option<String>,
+    /// Why the action is not satisfied, if known
+    pub reasons: Vec<String>,
+}
+
+impl ActionStateReport {
+    pub fn new(satisfied: bool) -> Self {
+        Self {
+            satisfied,
+            current: None,
+            desired: None,
+            reasons: vec![],
+        }
+    }
+}
+
+/// The phase of an install an [`Action`] belongs to, see [`Action::category`]
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum ActionCategory {
+    /// Preparing the system to hold `/nix`, such as creating a volume
+    Prepare,
+    /// Fetching and unpacking Nix into the store
+    Provision,
+    /// Creating the build users and group
+    Users,
+    /// Writing Nix configuration and shell profiles
+    Configuration,
+    /// Setting up and starting the Nix daemon
+    Service,
+    #[default]
+    Other,
+}
+
+/**
+A progress event emitted as an [`Action`](crate::action::Action) changes [`ActionState`],
+intended for UIs reporting on an [`InstallPlan::install_with_progress`](crate::InstallPlan::install_with_progress)
+*/
+#[derive(Debug, serde::Serialize, Clone)]
+pub struct ActionProgress {
+    /// The [`typetag::serde`] name of the action
+    pub action: String,
+    /// The [`action_id`][StatefulAction::action_id] of the action
+    pub action_id: String,
+    /// The [`tracing_synopsis`][Action::tracing_synopsis] of the action
+    pub synopsis: String,
+    /// The [`category`][Action::category] of the action
+    pub category: ActionCategory,
+    /// The state the action transitioned to
+    pub state: ActionState,
+    /// An optional human readable message, such as an error
+    pub message: Option<String>,
+}
+
+impl ActionProgress {
+    pub fn new(
+        action: &StatefulAction<Box<dyn Action>>,
+        state: ActionState,
+        message: impl Into<Option<String>>,
+    ) -> Self {
+        Self {
+            action: action.inner_typetag_name().to_string(),
+            action_id: action.action_id().to_string(),
+            synopsis: action.tracing_synopsis(),
+            category: action.category(),
+            state,
+            message: message.into(),
+        }
+    }
+}
+
 /// A 'tag' name an action has that corresponds to the one we serialize in [`typetag]`
 pub struct ActionTag(&'static str);
 
@@ -311,17 +493,25 @@ impl From<&'static str> for ActionTag {
 #[derive(Debug)]
 pub struct ActionError {
     action_tag: ActionTag,
-    kind: ActionErrorKind,
+    kind: Box<ActionErrorKind>,
+    redact_diagnostic: bool,
 }
 
 impl ActionError {
     pub fn new(action_tag: ActionTag, kind: impl Into<ActionErrorKind>) -> Self {
         Self {
             action_tag,
-            kind: kind.into(),
+            kind: Box::new(kind.into()),
+            redact_diagnostic: false,
         }
     }
 
+    /// Scrub home directory paths from the [`diagnostic`][ActionError::diagnostic] context of this error
+    pub fn redact_diagnostic(mut self, redact_diagnostic: bool) -> Self {
+        self.redact_diagnostic = redact_diagnostic;
+        self
+    }
+
     pub fn kind(&self) -> &ActionErrorKind {
         &self.kind
     }
@@ -330,10 +520,25 @@ impl ActionError {
         &self.action_tag
     }
 
+    /// The [`ErrorCode`] of the [`ActionErrorKind`] of this error
+    pub fn code(&self) -> &'static str {
+        self.kind.code()
+    }
+
+    /// Whether retrying could succeed, see [`ActionErrorKind::is_transient`]
+    pub fn is_transient(&self) -> bool {
+        self.kind.is_transient()
+    }
+
     #[cfg(feature = "diagnostics")]
     pub fn diagnostic(&self) -> String {
         use crate::diagnostics::ErrorDiagnostic;
-        self.kind.diagnostic()
+        let diagnostic = self.kind.diagnostic();
+        if self.redact_diagnostic {
+            crate::diagnostics::redact_home_paths(&diagnostic)
+        } else {
+            diagnostic
+        }
     }
 }
 
@@ -345,7 +550,7 @@ impl std::fmt::Display for ActionError {
 
 impl std::error::Error for ActionError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
-        Some(&self.kind)
+        Some(self.kind.as_ref())
     }
 }
 
@@ -355,6 +560,42 @@ impl From<ActionError> for ActionErrorKind {
     }
 }
 
+/// Whether the first I/O or HTTP error in the chain starting at `err` is worth retrying
+fn chain_is_transient(err: &(dyn Error + 'static)) -> bool {
+    let mut walker = Some(err);
+    while let Some(err) = walker {
+        if let Some(err) = err.downcast_ref::<reqwest::Error>() {
+            return err.is_timeout()
+                || err.is_connect()
+                || err.status().is_some_and(|status| {
+                    status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                });
+        }
+        if let Some(err) = err.downcast_ref::<std::io::Error>() {
+            return io_is_transient(err);
+        }
+        walker = err.source();
+    }
+    false
+}
+
+/// Whether an I/O error is the kind a retry could get past, as opposed to one like [`PermissionDenied`](std::io::ErrorKind::PermissionDenied)
+pub(crate) fn io_is_transient(err: &std::io::Error) -> bool {
+    use std::io::ErrorKind;
+    matches!(
+        err.kind(),
+        ErrorKind::TimedOut
+            | ErrorKind::Interrupted
+            | ErrorKind::WouldBlock
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::ConnectionRefused
+            | ErrorKind::NotConnected
+            | ErrorKind::BrokenPipe
+            | ErrorKind::UnexpectedEof
+    )
+}
+
 /// An error occurring during an action
 #[non_exhaustive]
 #[derive(thiserror::Error, Debug, strum::IntoStaticStr)]
@@ -391,6 +632,24 @@ pub enum ActionErrorKind {
         "`{0}` exists with different content than planned, consider removing it with `rm {0}`"
     )]
     DifferentContent(std::path::PathBuf),
+    /// The planned content of a path did not match its expected hash
+    #[error("Content planned for `{}` has hash `{found}`, expected `{expected}`", .path.display())]
+    HashMismatch {
+        path: std::path::PathBuf,
+        expected: String,
+        found: String,
+    },
+    /// The expected hash could not be understood
+    #[error("Could not parse `{0}` as a SHA-256 hash, expected `sha256:` followed by 64 hexadecimal characters")]
+    InvalidHash(String),
+    /// The markers delimiting a block in a file were unmatched, repeated, or nested
+    #[error("The `{begin}` and `{end}` markers in `{}` are malformed ({reason}), consider fixing them by hand", .path.display())]
+    MalformedMarkers {
+        path: std::path::PathBuf,
+        begin: String,
+        end: String,
+        reason: &'static str,
+    },
     /// The file already exists
     #[error("`{0}` already exists, consider removing it with `rm {0}`")]
     FileExists(std::path::PathBuf),
@@ -573,6 +832,31 @@ pub enum ActionErrorKind {
 }
 
 impl ActionErrorKind {
+    /// Whether this error is likely transient, such as a network or I/O timeout, so retrying could succeed
+    ///
+    /// Deterministic failures, like permission denied or invalid input, are not, so [`StatefulAction::try_execute`] fails fast on them instead of retrying.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Child(child) => child.is_transient(),
+            Self::MultipleChildren(children) => {
+                !children.is_empty() && children.iter().all(ActionError::is_transient)
+            },
+            Self::Multiple(kinds) => !kinds.is_empty() && kinds.iter().all(Self::is_transient),
+            Self::Custom(custom) => {
+                if let Some(err) = custom.downcast_ref::<base::CreateFileError>() {
+                    err.is_transient()
+                } else if let Some(err) = custom.downcast_ref::<base::FetchUrlError>() {
+                    err.is_transient()
+                } else if let Some(err) = custom.downcast_ref::<common::UpdateChannelsError>() {
+                    err.is_transient()
+                } else {
+                    chain_is_transient(custom.as_ref())
+                }
+            },
+            _ => chain_is_transient(self),
+        }
+    }
+
     pub fn command(command: &tokio::process::Command, error: std::io::Error) -> Self {
         Self::Command {
             #[cfg(feature = "diagnostics")]
@@ -620,6 +904,8 @@ impl crate::diagnostics::ErrorDiagnostic for ActionErrorKind {
             | Self::GettingMetadata(path, _)
             | Self::CreateDirectory(path, _)
             | Self::PathWasNotFile(path)
+            | Self::HashMismatch { path, .. }
+            | Self::MalformedMarkers { path, .. }
             | Self::Remove(path, _) => {
                 vec![path.to_string_lossy().to_string()]
             },
@@ -0,0 +1,62 @@
+/** Collapses runs of identical warnings into one, followed by "...repeated N times"
+
+Opt-in per log site: only warnings sent through the same [`RepeatedWarnings`] are collapsed, so critical messages
+logged directly with [`tracing::warn!`] are never suppressed. Warnings are emitted in the span current at the time
+of the call, including the repeat count emitted when the run ends (by a different warning, [`flush`](RepeatedWarnings::flush), or drop).
+ */
+#[derive(Debug, Default)]
+pub(crate) struct RepeatedWarnings {
+    last: Option<String>,
+    repeats: usize,
+}
+
+impl RepeatedWarnings {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Emit `message`, unless it is identical to the previous one
+    pub(crate) fn warn(&mut self, message: impl Into<String>) {
+        let message = message.into();
+        if self.last.as_ref() == Some(&message) {
+            self.repeats += 1;
+            return;
+        }
+        self.flush();
+        tracing::warn!("{message}");
+        self.last = Some(message);
+    }
+
+    /// End the current run, emitting how often its warning was repeated
+    pub(crate) fn flush(&mut self) {
+        if self.repeats > 0 {
+            tracing::warn!("...repeated {} times", self.repeats);
+        }
+        self.repeats = 0;
+        self.last = None;
+    }
+}
+
+impl Drop for RepeatedWarnings {
+    fn drop(&mut self) {
+        self.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn counts_identical_warnings() {
+        let mut warnings = RepeatedWarnings::new();
+        warnings.warn("Retrying");
+        warnings.warn("Retrying");
+        warnings.warn("Retrying");
+        assert_eq!(warnings.repeats, 2);
+
+        warnings.warn("Giving up");
+        assert_eq!(warnings.repeats, 0);
+        assert_eq!(warnings.last.as_deref(), Some("Giving up"));
+    }
+}
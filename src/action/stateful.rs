@@ -1,7 +1,12 @@
+use std::{path::PathBuf, time::Duration};
+
 use serde::{Deserialize, Serialize};
 use tracing::{Instrument, Span};
 
-use super::{Action, ActionDescription, ActionError, ActionTag};
+use super::{
+    Action, ActionCategory, ActionDescription, ActionError, ActionStateReport, ActionTag,
+    RepeatedWarnings,
+};
 
 /// A wrapper around an [`Action`](crate::action::Action) which tracks the [`ActionState`] and
 /// handles some tracing output
@@ -9,6 +14,16 @@ use super::{Action, ActionDescription, ActionError, ActionTag};
 pub struct StatefulAction<A> {
     pub(crate) action: A,
     pub(crate) state: ActionState,
+    /// Generated at plan time, so the events of one action can be correlated across execute and revert
+    #[serde(default = "new_action_id")]
+    pub(crate) id: String,
+    /// How long the last successful execute took
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) elapsed: Option<Duration>,
+}
+
+pub(crate) fn new_action_id() -> String {
+    uuid::Uuid::new_v4().to_string()
 }
 
 impl<A> From<A> for StatefulAction<A>
@@ -19,7 +34,35 @@ where
         Self {
             action,
             state: ActionState::Uncompleted,
+            id: new_action_id(),
+            elapsed: None,
+        }
+    }
+}
+
+impl<A> StatefulAction<A> {
+    /// A stable identifier for this action, recorded as the `action_id` field of its execute and revert spans
+    pub fn action_id(&self) -> &str {
+        &self.id
+    }
+
+    /// How long the action took to execute, if it was executed (rather than found already complete)
+    pub fn elapsed(&self) -> Option<Duration> {
+        self.elapsed
+    }
+
+    /// Move to `state`, warning on transitions [`ActionState::can_transition_to`] forbids
+    ///
+    /// The transition still happens, a library consumer's plan should not be brought down by a bookkeeping mistake.
+    pub(crate) fn set_state(&mut self, state: ActionState) {
+        if !self.state.can_transition_to(state) {
+            tracing::warn!(
+                action_id = %self.id,
+                "Illegal action state transition from {:?} to {state:?}",
+                self.state
+            );
         }
+        self.state = state;
     }
 }
 
@@ -30,6 +73,35 @@ impl StatefulAction<Box<dyn Action>> {
     pub fn tracing_synopsis(&self) -> String {
         self.action.tracing_synopsis()
     }
+    /// The phase of the install the action belongs to
+    pub fn category(&self) -> ActionCategory {
+        self.action.category()
+    }
+    /// The [`estimated_duration`][Action::estimated_duration] of the action, or `None` if it will not execute
+    pub fn estimated_duration(&self) -> Option<Duration> {
+        match self.state {
+            ActionState::Completed | ActionState::Skipped => None,
+            _ => self.action.estimated_duration(),
+        }
+    }
+    /// The [`state_report`][Action::state_report] of the action
+    pub async fn state_report(&self) -> Result<ActionStateReport, ActionError> {
+        self.action.state_report().await
+    }
+    /// The [`paths_written`][Action::paths_written] by the action, or none if it was skipped
+    pub fn paths_written(&self) -> Vec<PathBuf> {
+        match self.state {
+            ActionState::Skipped => vec![],
+            _ => self.action.paths_written(),
+        }
+    }
+    /// The [`paths_read`][Action::paths_read] by the action, or none if it was skipped
+    pub fn paths_read(&self) -> Vec<PathBuf> {
+        match self.state {
+            ActionState::Skipped => vec![],
+            _ => self.action.paths_read(),
+        }
+    }
     /// A tracing span suitable for the action
     pub fn tracing_span(&self) -> Span {
         self.action.tracing_span()
@@ -55,7 +127,7 @@ impl StatefulAction<Box<dyn Action>> {
     /// Perform any execution steps
     ///
     /// You should prefer this ([`try_execute`][StatefulAction::try_execute]) over [`execute`][Action::execute] as it handles [`ActionState`] and does tracing
-    #[tracing::instrument(level = "debug", skip_all)]
+    #[tracing::instrument(level = "debug", skip_all, fields(action_id = %self.id))]
     pub async fn try_execute(&mut self) -> Result<(), ActionError> {
         match self.state {
             ActionState::Completed => {
@@ -69,20 +141,48 @@ impl StatefulAction<Box<dyn Action>> {
                 tracing::trace!("Skipped: {}", self.action.tracing_synopsis());
                 Ok(())
             },
+            // Only actions not yet started by this install, resumed ones are executed again
+            ActionState::Uncompleted if self.action.already_satisfied().await? => {
+                tracing::debug!(
+                    "Completed: (Already satisfied) {}",
+                    self.action.tracing_synopsis()
+                );
+                // The same as an action planned when the system already matched, which is reverted on uninstall
+                self.set_state(ActionState::Completed);
+                Ok(())
+            },
             _ => {
-                self.state = ActionState::Progress;
+                self.set_state(ActionState::Progress);
                 tracing::debug!("Executing: {}", self.action.tracing_synopsis());
-                self.action.execute().await?;
-                self.state = ActionState::Completed;
+                let start = std::time::Instant::now();
+                execute_with_retries(&mut *self.action).await?;
+                self.action.verify().await?;
+                self.elapsed = Some(start.elapsed());
+                self.set_state(ActionState::Completed);
                 tracing::debug!("Completed: {}", self.action.tracing_synopsis());
                 Ok(())
             },
         }
     }
+    /// Log the side effects of any execution steps, without performing them
+    ///
+    /// You should prefer this ([`try_dry_run`][StatefulAction::try_dry_run]) over [`dry_run`][Action::dry_run] as it skips completed actions
+    #[tracing::instrument(level = "debug", skip_all)]
+    pub async fn try_dry_run(&self) {
+        match self.state {
+            ActionState::Completed | ActionState::Skipped => {
+                tracing::trace!(
+                    "Would skip: (Already done) {}",
+                    self.action.tracing_synopsis()
+                );
+            },
+            _ => self.action.dry_run().await,
+        }
+    }
     /// Perform any revert steps
     ///
     /// You should prefer this ([`try_revert`][StatefulAction::try_revert]) over [`revert`][Action::revert] as it handles [`ActionState`] and does tracing
-    #[tracing::instrument(level = "debug", skip_all)]
+    #[tracing::instrument(level = "debug", skip_all, fields(action_id = %self.id))]
     pub async fn try_revert(&mut self) -> Result<(), ActionError> {
         match self.state {
             ActionState::Uncompleted => {
@@ -97,11 +197,11 @@ impl StatefulAction<Box<dyn Action>> {
                 Ok(())
             },
             _ => {
-                self.state = ActionState::Progress;
+                self.set_state(ActionState::Progress);
                 tracing::debug!("Reverting: {}", self.action.tracing_synopsis());
                 self.action.revert().await?;
                 tracing::debug!("Reverted: {}", self.action.tracing_synopsis());
-                self.state = ActionState::Uncompleted;
+                self.set_state(ActionState::Uncompleted);
                 Ok(())
             },
         }
@@ -131,6 +231,35 @@ where
         &self.action
     }
 
+    /// The [`state_report`][Action::state_report] of the action
+    pub async fn state_report(&self) -> Result<ActionStateReport, ActionError> {
+        self.action.state_report().await
+    }
+
+    /// The [`paths_written`][Action::paths_written] by the action, or none if it was skipped
+    pub fn paths_written(&self) -> Vec<PathBuf> {
+        match self.state {
+            ActionState::Skipped => vec![],
+            _ => self.action.paths_written(),
+        }
+    }
+
+    /// The [`paths_read`][Action::paths_read] by the action, or none if it was skipped
+    pub fn paths_read(&self) -> Vec<PathBuf> {
+        match self.state {
+            ActionState::Skipped => vec![],
+            _ => self.action.paths_read(),
+        }
+    }
+
+    /// The [`estimated_duration`][Action::estimated_duration] of the action, or `None` if it will not execute
+    pub fn estimated_duration(&self) -> Option<Duration> {
+        match self.state {
+            ActionState::Completed | ActionState::Skipped => None,
+            _ => self.action.estimated_duration(),
+        }
+    }
+
     pub fn boxed(self) -> StatefulAction<Box<dyn Action>>
     where
         Self: 'static,
@@ -138,25 +267,32 @@ where
         StatefulAction {
             action: Box::new(self.action),
             state: self.state,
+            id: self.id,
+            elapsed: self.elapsed,
         }
     }
     /// A description of what this action would do during execution
     pub fn describe_execute(&self) -> Vec<ActionDescription> {
-        if self.state == ActionState::Completed {
-            return vec![];
+        match self.state {
+            ActionState::Completed | ActionState::Skipped => {
+                vec![]
+            },
+            _ => self.action.execute_description(),
         }
-        self.action.execute_description()
     }
     /// A description of what this action would do during revert
     pub fn describe_revert(&self) -> Vec<ActionDescription> {
-        if self.state == ActionState::Uncompleted {
-            return vec![];
+        match self.state {
+            ActionState::Uncompleted | ActionState::Skipped => {
+                vec![]
+            },
+            _ => self.action.revert_description(),
         }
-        self.action.revert_description()
     }
     /// Perform any execution steps
     ///
     /// You should prefer this ([`try_execute`][StatefulAction::try_execute]) over [`execute`][Action::execute] as it handles [`ActionState`] and does tracing
+    #[tracing::instrument(level = "debug", skip_all, fields(action_id = %self.id))]
     pub async fn try_execute(&mut self) -> Result<(), ActionError> {
         let span = self.action.tracing_span();
         match self.state {
@@ -172,15 +308,35 @@ where
                 tracing::trace!(parent: &span, "Skipped: {}", self.action.tracing_synopsis());
                 Ok(())
             },
+            ActionState::Uncompleted
+                if self
+                    .action
+                    .already_satisfied()
+                    .instrument(span.clone())
+                    .await? =>
+            {
+                tracing::debug!(
+                    parent: &span,
+                    "Completed: (Already satisfied) {}",
+                    self.action.tracing_synopsis()
+                );
+                self.set_state(ActionState::Completed);
+                Ok(())
+            },
             _ => {
-                self.state = ActionState::Progress;
+                self.set_state(ActionState::Progress);
                 tracing::debug!(
                     parent: &span,
                     "Executing: {}",
                     self.action.tracing_synopsis()
                 );
-                self.action.execute().instrument(span.clone()).await?;
-                self.state = ActionState::Completed;
+                let start = std::time::Instant::now();
+                execute_with_retries(&mut self.action)
+                    .instrument(span.clone())
+                    .await?;
+                self.action.verify().instrument(span.clone()).await?;
+                self.elapsed = Some(start.elapsed());
+                self.set_state(ActionState::Completed);
                 tracing::debug!(
                     parent: &span,
                     "Completed: {}",
@@ -190,9 +346,26 @@ where
             },
         }
     }
+    /// Log the side effects of any execution steps, without performing them
+    ///
+    /// You should prefer this ([`try_dry_run`][StatefulAction::try_dry_run]) over [`dry_run`][Action::dry_run] as it skips completed actions
+    pub async fn try_dry_run(&self) {
+        let span = self.action.tracing_span();
+        match self.state {
+            ActionState::Completed | ActionState::Skipped => {
+                tracing::trace!(
+                    parent: &span,
+                    "Would skip: (Already done) {}",
+                    self.action.tracing_synopsis()
+                );
+            },
+            _ => self.action.dry_run().instrument(span.clone()).await,
+        }
+    }
     /// Perform any revert steps
     ///
     /// You should prefer this ([`try_revert`][StatefulAction::try_revert]) over [`revert`][Action::revert] as it handles [`ActionState`] and does tracing
+    #[tracing::instrument(level = "debug", skip_all, fields(action_id = %self.id))]
     pub async fn try_revert(&mut self) -> Result<(), ActionError> {
         let span = self.action.tracing_span();
         match self.state {
@@ -209,7 +382,7 @@ where
                 Ok(())
             },
             _ => {
-                self.state = ActionState::Progress;
+                self.set_state(ActionState::Progress);
                 tracing::debug!(
                     parent: &span,
                     "Reverting: {}",
@@ -221,7 +394,7 @@ where
                     "Reverted: {}",
                     self.action.tracing_synopsis()
                 );
-                self.state = ActionState::Uncompleted;
+                self.set_state(ActionState::Uncompleted);
                 Ok(())
             },
         }
@@ -231,6 +404,8 @@ where
         Self {
             state: ActionState::Completed,
             action,
+            id: new_action_id(),
+            elapsed: None,
         }
     }
 
@@ -238,6 +413,8 @@ where
         Self {
             state: ActionState::Skipped,
             action,
+            id: new_action_id(),
+            elapsed: None,
         }
     }
 
@@ -245,6 +422,38 @@ where
         Self {
             state: ActionState::Uncompleted,
             action,
+            id: new_action_id(),
+            elapsed: None,
+        }
+    }
+}
+
+async fn execute_with_retries(action: &mut dyn Action) -> Result<(), ActionError> {
+    let max_retries = action.max_retries();
+    let mut attempt = 0;
+    // An action failing the same way on every attempt should not flood the log
+    let mut warnings = RepeatedWarnings::new();
+    loop {
+        match action.execute().await {
+            Ok(()) => return Ok(()),
+            // Deterministic failures, like permission denied, would only fail again
+            Err(err) if attempt < max_retries && err.is_transient() => {
+                let delay = action.retry_base_delay() * 2u32.pow(attempt);
+                attempt += 1;
+                warnings.warn(format!(
+                    "Retrying `{}` after error: {err}",
+                    action.tracing_synopsis()
+                ));
+                // Varies by attempt, so it would defeat the collapsing above
+                tracing::debug!(
+                    attempt,
+                    max_retries,
+                    "Retry attempt {attempt} of {max_retries} in {}s",
+                    delay.as_secs_f32()
+                );
+                tokio::time::sleep(delay).await;
+            },
+            Err(err) => return Err(err),
         }
     }
 }
@@ -278,3 +487,137 @@ pub enum ActionState {
     */
     Skipped,
 }
+
+impl ActionState {
+    /** Whether an [`Action`](crate::action::Action) in this state may move to `next`
+
+    Executing moves [`Uncompleted`](ActionState::Uncompleted) through [`Progress`](ActionState::Progress) to
+    [`Completed`](ActionState::Completed), and reverting moves back the same way. An action which has yet to
+    complete may be [`Skipped`](ActionState::Skipped) instead, such as by [`InstallPlan::retain_actions`](crate::InstallPlan::retain_actions), which is final. Deserialized receipts may hold any state, this is only checked when the state changes.
+    */
+    pub fn can_transition_to(self, next: ActionState) -> bool {
+        use ActionState::*;
+        matches!(
+            (self, next),
+            (Uncompleted, Progress | Completed | Skipped)
+                | (Progress, Completed | Uncompleted | Skipped)
+                | (Completed, Progress)
+        ) || self == next
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::action::ActionErrorKind;
+
+    #[derive(Debug, serde::Deserialize, serde::Serialize, Clone)]
+    struct FailsOnce {
+        attempts: u32,
+    }
+
+    #[async_trait::async_trait]
+    #[typetag::serde(name = "fails_once")]
+    impl Action for FailsOnce {
+        fn action_tag() -> ActionTag {
+            ActionTag("fails_once")
+        }
+        fn tracing_synopsis(&self) -> String {
+            "Fail once".to_string()
+        }
+        fn tracing_span(&self) -> Span {
+            tracing::span!(tracing::Level::DEBUG, "fails_once")
+        }
+        fn execute_description(&self) -> Vec<ActionDescription> {
+            vec![]
+        }
+        async fn execute(&mut self) -> Result<(), ActionError> {
+            self.attempts += 1;
+            if self.attempts == 1 {
+                return Err(Self::error(ActionErrorKind::Read(
+                    "/dev/null".into(),
+                    std::io::ErrorKind::TimedOut.into(),
+                )));
+            }
+            Ok(())
+        }
+        fn max_retries(&self) -> u32 {
+            1
+        }
+        fn retry_base_delay(&self) -> Duration {
+            Duration::ZERO
+        }
+        async fn already_satisfied(&self) -> Result<bool, ActionError> {
+            Ok(self.attempts > 0)
+        }
+        fn revert_description(&self) -> Vec<ActionDescription> {
+            vec![]
+        }
+        async fn revert(&mut self) -> Result<(), ActionError> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn retries_failed_execute() -> Result<(), ActionError> {
+        let mut action = FailsOnce { attempts: 0 }.stateful();
+
+        action.try_execute().await?;
+
+        assert_eq!(action.inner().attempts, 2);
+        assert_eq!(action.state, ActionState::Completed);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn only_skips_execute_for_unstarted_actions() -> Result<(), ActionError> {
+        let mut action = FailsOnce { attempts: 1 }.stateful();
+        action.try_execute().await?;
+        assert_eq!(action.inner().attempts, 1, "Satisfied, so not executed");
+        assert_eq!(action.state, ActionState::Completed);
+
+        // Resumed after an earlier run stopped part way through executing it
+        let mut action = FailsOnce { attempts: 1 }.stateful();
+        action.state = ActionState::Progress;
+        action.try_execute().await?;
+        assert_eq!(action.inner().attempts, 2, "Resumed, so executed again");
+        assert_eq!(action.state, ActionState::Completed);
+
+        Ok(())
+    }
+
+    #[test]
+    fn classifies_transient_errors() {
+        let timed_out =
+            ActionErrorKind::Read("/dev/null".into(), std::io::ErrorKind::TimedOut.into());
+        assert!(timed_out.is_transient());
+        let denied = ActionErrorKind::Read(
+            "/dev/null".into(),
+            std::io::ErrorKind::PermissionDenied.into(),
+        );
+        assert!(!denied.is_transient());
+        assert!(!ActionErrorKind::UnknownUrlScheme.is_transient());
+        let declined =
+            ActionErrorKind::from(crate::action::base::CreateFileError::OverwriteDeclined {
+                path: "/dev/null".into(),
+            });
+        assert!(!declined.is_transient());
+    }
+
+    #[tokio::test]
+    async fn validates_state_transitions() -> Result<(), ActionError> {
+        assert!(!ActionState::Completed.can_transition_to(ActionState::Uncompleted));
+        assert!(!ActionState::Skipped.can_transition_to(ActionState::Progress));
+        assert!(ActionState::Uncompleted.can_transition_to(ActionState::Completed));
+        assert!(ActionState::Uncompleted.can_transition_to(ActionState::Skipped));
+        assert!(!ActionState::Completed.can_transition_to(ActionState::Skipped));
+
+        let mut action = FailsOnce { attempts: 1 }.stateful();
+        action.try_execute().await?;
+        action.try_revert().await?;
+        assert_eq!(action.state, ActionState::Uncompleted);
+
+        Ok(())
+    }
+}
@@ -1,27 +1,67 @@
-use std::process::ExitCode;
+use std::{process::ExitCode, time::Duration};
 
-use clap::Parser;
+use clap::{ArgAction, Parser};
 
 use crate::{cli::CommandExecute, NixInstallerError};
 
 /// Run a self test of Nix to ensure that an install is working
 #[derive(Debug, Parser)]
-pub struct SelfTest {}
+pub struct SelfTest {
+    /// Print the results as JSON on stdout instead of logging them
+    #[clap(
+        long,
+        env = "NIX_INSTALLER_JSON",
+        action(ArgAction::SetTrue),
+        default_value = "false"
+    )]
+    pub json: bool,
+    /// How many seconds each command of the self-test may take before it is considered hung
+    #[clap(
+        long,
+        env = "NIX_INSTALLER_SELF_TEST_TIMEOUT",
+        default_value_t = crate::self_test::DEFAULT_SELF_TEST_TIMEOUT.as_secs()
+    )]
+    pub timeout: u64,
+}
 
 #[async_trait::async_trait]
 impl CommandExecute for SelfTest {
     #[tracing::instrument(level = "debug", skip_all, fields())]
     async fn execute(self) -> eyre::Result<ExitCode> {
-        crate::self_test::self_test()
-            .await
-            .map_err(NixInstallerError::SelfTest)?;
+        let Self { json, timeout } = self;
+
+        let report = crate::self_test::self_test_report_with(
+            &crate::self_test::Shell::discover(),
+            crate::self_test::flakes_enabled(),
+            Duration::from_secs(timeout),
+        )
+        .await;
+        if json {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            return Ok(if report.passed() {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            });
+        }
+        let summary = report.summary();
+        let tested = report.tested();
+        if summary.failed != 0 {
+            tracing::warn!("{summary}");
+        }
+        // The daemon is tested even when no shells are found
+        report.into_result().map_err(NixInstallerError::SelfTest)?;
+        if tested == 0 {
+            tracing::warn!("No shells were found to test the Nix install in.");
+            return Ok(ExitCode::SUCCESS);
+        }
 
         tracing::info!(
             shells = ?crate::self_test::Shell::discover()
                 .iter()
                 .map(|v| v.executable())
                 .collect::<Vec<_>>(),
-            "Successfully tested Nix install in all discovered shells."
+            "Successfully tested Nix install, {summary}."
         );
         Ok(ExitCode::SUCCESS)
     }
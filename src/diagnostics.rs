@@ -5,14 +5,21 @@ When enabled with the `diagnostics` feature (default) this module provides autom
 That endpoint can be a URL such as `https://our.project.org/nix-installer/diagnostics` or `file:///home/$USER/diagnostic.json` which receives a [`DiagnosticReport`] in JSON format.
 */
 
-use std::{path::PathBuf, time::Duration};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use os_release::OsRelease;
 use reqwest::Url;
 
 use crate::{
-    action::ActionError, parse_ssl_cert, planner::PlannerError, settings::InstallSettingsError,
-    CertificateError, NixInstallerError,
+    action::ActionError,
+    parse_ssl_cert,
+    planner::PlannerError,
+    self_test::{DiscoveredShell, SelfTestReport, Shell},
+    settings::InstallSettingsError,
+    CertificateError, InstallPlan, NixInstallerError,
 };
 
 /// The static of an action attempt
@@ -106,11 +113,11 @@ impl DiagnosticData {
         let mut walker: &dyn std::error::Error = &err;
         while let Some(source) = walker.source() {
             if let Some(downcasted) = source.downcast_ref::<ActionError>() {
-                let downcasted_diagnostic = downcasted.kind().diagnostic();
+                let downcasted_diagnostic = downcasted.diagnostic();
                 failure_chain.push(downcasted_diagnostic);
             }
             if let Some(downcasted) = source.downcast_ref::<Box<ActionError>>() {
-                let downcasted_diagnostic = downcasted.kind().diagnostic();
+                let downcasted_diagnostic = downcasted.diagnostic();
                 failure_chain.push(downcasted_diagnostic);
             }
             if let Some(downcasted) = source.downcast_ref::<PlannerError>() {
@@ -214,6 +221,55 @@ impl DiagnosticData {
     }
 }
 
+/// A bundle of everything useful for a support ticket, written as a single JSON file by [`DiagnosticBundle::write`]
+#[derive(Debug, serde::Serialize)]
+pub struct DiagnosticBundle {
+    pub version: String,
+    pub triple: String,
+    pub shells: Vec<DiscoveredShell>,
+    pub self_test: SelfTestReport,
+    pub plan: Option<serde_json::Value>,
+    /// The failure chain of each captured error, as produced by [`DiagnosticData::failure`]
+    pub failures: Vec<Vec<String>>,
+}
+
+impl DiagnosticBundle {
+    /// Discover shells, run the self test, and capture the given `plan` and `errors`
+    #[tracing::instrument(level = "debug", skip_all)]
+    pub async fn collect(
+        plan: Option<&InstallPlan>,
+        errors: &[&NixInstallerError],
+    ) -> Result<Self, DiagnosticError> {
+        let plan = plan.map(serde_json::to_value).transpose()?;
+        let failures = errors
+            .iter()
+            .filter_map(|err| DiagnosticData::default().failure(err).failure_chain)
+            .collect();
+        Ok(Self {
+            version: env!("CARGO_PKG_VERSION").into(),
+            triple: target_lexicon::HOST.to_string(),
+            shells: Shell::discover_with_versions().await,
+            self_test: crate::self_test::self_test_report().await,
+            plan,
+            failures,
+        })
+    }
+
+    /// Write the bundle to `path`, replacing any home directory paths with `~` if `redact` is set
+    #[tracing::instrument(level = "debug", skip_all, fields(path = %path.as_ref().display()))]
+    pub async fn write(&self, path: impl AsRef<Path>, redact: bool) -> Result<(), DiagnosticError> {
+        let path = path.as_ref();
+        let mut serialized = serde_json::to_string_pretty(self)?;
+        if redact {
+            serialized = redact_home_paths(&serialized);
+        }
+        tracing::debug!("Writing diagnostic bundle to `{}`", path.display());
+        tokio::fs::write(path, serialized)
+            .await
+            .map_err(|e| DiagnosticError::Write(path.to_path_buf(), e))
+    }
+}
+
 #[non_exhaustive]
 #[derive(thiserror::Error, Debug, strum::IntoStaticStr)]
 pub enum DiagnosticError {
@@ -255,6 +311,68 @@ impl ErrorDiagnostic for DiagnosticError {
     }
 }
 
+/// Replace any path inside a home directory (`$HOME`, `/home/<user>`, `/Users/<user>`) in `value` with one relative to `~`
+pub fn redact_home_paths(value: &str) -> String {
+    redact_home_paths_with(value, std::env::var("HOME").ok().as_deref())
+}
+
+/// Like [`redact_home_paths`], with `home` in place of `$HOME`
+///
+/// Only whole path components are replaced, so `/root` leaves `/rootfs` and `/mnt/root` alone.
+fn redact_home_paths_with(value: &str, home: Option<&str>) -> String {
+    let mut redacted = value.to_string();
+    if let Some(home) = home
+        .map(|home| home.trim_end_matches('/'))
+        .filter(|home| !home.is_empty())
+    {
+        redacted = redact_home_prefix(&redacted, home, false);
+    }
+    for prefix in ["/home/", "/Users/"] {
+        redacted = redact_home_prefix(&redacted, prefix, true);
+    }
+    redacted
+}
+
+/// Whether `c` ends a path component, as far as [`redact_home_paths`] can tell
+fn ends_component(c: char) -> bool {
+    c == '/' || c == '"' || c == '\'' || c.is_whitespace()
+}
+
+/// Replace `prefix` (followed by a user name, if `with_user`) with `~` wherever it starts a path
+fn redact_home_prefix(value: &str, prefix: &str, with_user: bool) -> String {
+    let mut scrubbed = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(idx) = rest.find(prefix) {
+        let starts_path = rest[..idx]
+            .chars()
+            .next_back()
+            .or_else(|| scrubbed.chars().next_back())
+            .is_none_or(|c| !(c.is_alphanumeric() || matches!(c, '/' | '.' | '-' | '_' | '~')));
+        let after_prefix = &rest[idx + prefix.len()..];
+        let user_len = match with_user {
+            true => after_prefix
+                .find(ends_component)
+                .unwrap_or(after_prefix.len()),
+            false => 0,
+        };
+        let ends_path = after_prefix[user_len..]
+            .chars()
+            .next()
+            .is_none_or(ends_component);
+        if starts_path && ends_path && (user_len > 0 || !with_user) {
+            scrubbed.push_str(&rest[..idx]);
+            scrubbed.push('~');
+            rest = &after_prefix[user_len..];
+        } else {
+            // Every prefix starts with an ASCII `/`, so this is a character boundary
+            scrubbed.push_str(&rest[..idx + 1]);
+            rest = &rest[idx + 1..];
+        }
+    }
+    scrubbed.push_str(rest);
+    scrubbed
+}
+
 pub fn diagnostic_endpoint_parser(input: &str) -> Result<Option<Url>, DiagnosticError> {
     match Url::parse(input) {
         Ok(v) => match v.scheme() {
@@ -275,3 +393,55 @@ pub fn diagnostic_endpoint_validator(input: &str) -> Result<String, DiagnosticEr
     let _ = diagnostic_endpoint_parser(input)?;
     Ok(input.to_string())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn redacts_home_paths() {
+        assert_eq!(
+            redact_home_paths(r#"Read("/home/ana/.nix-channels", "/Users/bo/x")"#),
+            r#"Read("~/.nix-channels", "~/x")"#
+        );
+        assert_eq!(
+            redact_home_paths(r#"Read("/etc/nix/nix.conf")"#),
+            r#"Read("/etc/nix/nix.conf")"#
+        );
+    }
+
+    #[test]
+    fn redacts_only_whole_home_components() {
+        assert_eq!(
+            redact_home_paths_with(
+                r#"Read("/root/.nix-channels", "/rootfs/x", "/nix/store/abc-root/x") /root"#,
+                Some("/root/")
+            ),
+            r#"Read("~/.nix-channels", "/rootfs/x", "/nix/store/abc-root/x") ~"#
+        );
+        assert_eq!(
+            redact_home_paths_with(
+                r#"Read("/var/home/ana/x", "/nix/store/abc-src/home/nix", "/home/")"#,
+                None
+            ),
+            r#"Read("/var/home/ana/x", "/nix/store/abc-src/home/nix", "/home/")"#
+        );
+    }
+
+    #[tokio::test]
+    async fn writes_redacted_bundle() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let path = temp_dir.path().join("bundle.json");
+        let mut bundle = DiagnosticBundle::collect(None, &[]).await?;
+        bundle.failures = vec![vec![r#"Read("/home/ana/.nix-channels")"#.into()]];
+
+        bundle.write(&path, true).await?;
+        let written: serde_json::Value =
+            serde_json::from_str(&tokio::fs::read_to_string(&path).await?)?;
+        assert_eq!(written["failures"][0][0], r#"Read("~/.nix-channels")"#);
+        assert!(written["plan"].is_null());
+        assert_eq!(written["version"], env!("CARGO_PKG_VERSION"));
+
+        Ok(())
+    }
+}
@@ -35,6 +35,15 @@ pub enum NixInstallerError {
     /// An error while writing the [`InstallPlan`](crate::InstallPlan)
     #[error("Recording install receipt")]
     RecordingReceipt(PathBuf, #[source] std::io::Error),
+    /// An error while reading a previously recorded [`InstallPlan`](crate::InstallPlan)
+    #[error("Reading install receipt `{}`", .0.display())]
+    ReadingReceipt(PathBuf, #[source] std::io::Error),
+    /// An error while deserializing a previously recorded [`InstallPlan`](crate::InstallPlan)
+    #[error("Deserializing install receipt `{}`, it may be from an incompatible version of `nix-installer`", .0.display())]
+    DeserializingReceipt(PathBuf, #[source] serde_json::Error),
+    /// A previously recorded [`InstallPlan`](crate::InstallPlan) contains an action this build does not have, such as one behind a disabled feature
+    #[error("Install receipt `{}` contains the action `{action}`, which is not supported in this build of `nix-installer`, it may need to be built with more features enabled", .path.display())]
+    UnsupportedAction { path: PathBuf, action: String },
     /// An error while writing copying the binary into the `/nix` folder
     #[error("Copying `nix-installer` binary into `/nix`")]
     CopyingSelf(
@@ -52,6 +61,12 @@ pub enum NixInstallerError {
     /// An error occurring when a signal is issued along [`InstallPlan::install`](crate::InstallPlan::install)'s `cancel_channel` argument
     #[error("Cancelled by user")]
     Cancelled,
+    /// A second signal during [`InstallPlan::install_with_signals`](crate::InstallPlan::install_with_signals), which stops reverting part way
+    #[error("Interrupted by a second signal, some actions were left unreverted")]
+    Interrupted,
+    /// An error while installing the signal handlers of [`InstallPlan::install_with_signals`](crate::InstallPlan::install_with_signals)
+    #[error("Installing signal handler")]
+    SignalHandler(#[source] std::io::Error),
     /// Semver error
     #[error("Semantic Versioning error")]
     SemVer(
@@ -66,6 +81,13 @@ pub enum NixInstallerError {
         #[source]
         PlannerError,
     ),
+    /// Plan validation error
+    #[error("Plan validation error")]
+    PlanValidation(
+        #[from]
+        #[source]
+        crate::plan::PlanValidationError,
+    ),
     /// Install setting error
     #[error("Install setting error")]
     InstallSettings(
@@ -88,6 +110,9 @@ pub enum NixInstallerError {
     /// Could not parse `nix-installer`'s version as a valid version according to Semantic Versioning, therefore the plan version compatibility cannot be checked
     #[error("Could not parse `nix-installer`'s version `{0}` as a valid version according to Semantic Versioning, therefore the plan version compatibility cannot be checked")]
     InvalidCurrentVersion(String, semver::Error),
+    /// Multiple actions in the plan create the same file
+    #[error("Multiple actions create `{}`:\n{}", .path.display(), .actions.iter().map(|action| format!("* {action}")).collect::<Vec<_>>().join("\n"))]
+    PlanConflict { path: PathBuf, actions: Vec<String> },
     /// This version of `nix-installer` is not compatible with this plan's version
     #[error("`nix-installer` version `{}` is not compatible with this plan's version `{}`", .binary, .plan)]
     IncompatibleVersion { binary: Version, plan: Version },
@@ -104,17 +129,24 @@ impl HasExpectedErrors for NixInstallerError {
             NixInstallerError::ActionRevert(_) => None,
             this @ NixInstallerError::SelfTest(_) => Some(Box::new(this)),
             NixInstallerError::RecordingReceipt(_, _) => None,
+            NixInstallerError::ReadingReceipt(_, _) => None,
+            this @ NixInstallerError::DeserializingReceipt(_, _) => Some(Box::new(this)),
+            this @ NixInstallerError::UnsupportedAction { .. } => Some(Box::new(this)),
             NixInstallerError::CopyingSelf(_) => None,
             NixInstallerError::SerializingReceipt(_) => None,
             this @ NixInstallerError::Cancelled => Some(Box::new(this)),
+            this @ NixInstallerError::Interrupted => Some(Box::new(this)),
+            NixInstallerError::SignalHandler(_) => None,
             NixInstallerError::SemVer(_) => None,
             NixInstallerError::Planner(planner_error) => planner_error.expected(),
+            NixInstallerError::PlanValidation(validation_error) => Some(Box::new(validation_error)),
             NixInstallerError::InstallSettings(_) => None,
             this @ NixInstallerError::InvalidVersionRequirement(_, _) => Some(Box::new(this)),
             this @ NixInstallerError::InvalidCurrentVersion(_, _) => Some(Box::new(this)),
             this @ NixInstallerError::IncompatibleVersion { binary: _, plan: _ } => {
                 Some(Box::new(this))
             },
+            this @ NixInstallerError::PlanConflict { .. } => Some(Box::new(this)),
             #[cfg(feature = "diagnostics")]
             NixInstallerError::Diagnostic(_) => None,
         }
@@ -135,6 +167,11 @@ impl crate::diagnostics::ErrorDiagnostic for NixInstallerError {
                 .iter()
                 .map(|action_error| action_error.diagnostic())
                 .collect(),
+            Self::PlanConflict { path, .. } => vec![path.to_string_lossy().to_string()],
+            Self::UnsupportedAction { action, .. } => vec![action.clone()],
+            Self::PlanValidation(validation_error) => {
+                vec![<&'static str>::from(validation_error).to_string()]
+            },
             _ => vec![],
         };
         format!(
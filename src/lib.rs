@@ -81,13 +81,15 @@ pub mod planner;
 pub mod self_test;
 pub mod settings;
 
-use std::{ffi::OsStr, path::Path, process::Output};
+use std::{ffi::OsStr, path::Path, process::Output, time::Duration};
 
 pub use error::NixInstallerError;
-pub use plan::InstallPlan;
+pub use plan::{
+    plan_diff, ActionProgressStream, InstallPlan, InstallTask, PathFootprint, PlanValidationError,
+};
 use planner::BuiltinPlanner;
 
-use reqwest::Certificate;
+use reqwest::{Certificate, Url};
 use tokio::process::Command;
 
 use crate::action::{Action, ActionErrorKind};
@@ -114,6 +116,30 @@ fn set_env(k: impl AsRef<OsStr>, v: impl AsRef<OsStr>) {
     std::env::set_var(k.as_ref(), v.as_ref());
 }
 
+/// Build the HTTP client used for fetches, honoring the configured proxy, SSL cert, and timeout
+///
+/// Library users needing more control (for example, a corporate proxy setup) can build their own
+/// [`reqwest::Client`] and pass it to actions like [`FetchAndUnpackNix`](crate::action::base::FetchAndUnpackNix) instead.
+pub async fn http_client(
+    proxy: Option<&Url>,
+    ssl_cert_file: Option<&Path>,
+    timeout: Option<Duration>,
+) -> Result<reqwest::Client, ActionErrorKind> {
+    let mut buildable_client = reqwest::Client::builder();
+    if let Some(proxy) = proxy {
+        buildable_client = buildable_client
+            .proxy(reqwest::Proxy::all(proxy.clone()).map_err(ActionErrorKind::Reqwest)?)
+    }
+    if let Some(ssl_cert_file) = ssl_cert_file {
+        let ssl_cert = parse_ssl_cert(ssl_cert_file).await?;
+        buildable_client = buildable_client.add_root_certificate(ssl_cert);
+    }
+    if let Some(timeout) = timeout {
+        buildable_client = buildable_client.timeout(timeout);
+    }
+    buildable_client.build().map_err(ActionErrorKind::Reqwest)
+}
+
 async fn parse_ssl_cert(ssl_cert_file: &Path) -> Result<Certificate, CertificateError> {
     let cert_buf = tokio::fs::read(ssl_cert_file)
         .await
@@ -1,15 +1,23 @@
-use std::{path::PathBuf, str::FromStr};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
 
 use crate::{
-    action::{Action, ActionDescription, StatefulAction},
+    action::{Action, ActionDescription, ActionProgress, ActionState, StatefulAction},
     planner::{BuiltinPlanner, Planner},
     NixInstallerError,
 };
 use owo_colors::OwoColorize;
 use semver::{Version, VersionReq};
-use tokio::sync::broadcast::Receiver;
+use tokio::sync::{broadcast::Receiver, mpsc::Sender};
+use tokio_util::sync::CancellationToken;
 
 pub const RECEIPT_LOCATION: &str = "/nix/receipt.json";
+/// A `:` separated list of directories which replaces the [`Planner::allowed_roots`] checked by [`check_allowed_roots`], ignored if empty
+pub const ALLOWED_ROOTS_ENV: &str = "NIX_INSTALLER_ALLOWED_ROOTS";
 
 /**
 A set of [`Action`]s, along with some metadata, which can be carried out to drive an install or
@@ -36,6 +44,8 @@ impl InstallPlan {
 
         let planner = planner.boxed();
         let actions = planner.plan().await?;
+        check_conflicts(&actions)?;
+        check_allowed_roots(&actions, &allowed_roots(planner.as_ref()))?;
 
         Ok(Self {
             planner,
@@ -57,6 +67,8 @@ impl InstallPlan {
         planner.pre_install_check().await?;
 
         let actions = planner.plan().await?;
+        check_conflicts(&actions)?;
+        check_allowed_roots(&actions, &allowed_roots(&planner))?;
         Ok(Self {
             planner: planner.boxed(),
             actions,
@@ -154,22 +166,32 @@ impl InstallPlan {
     pub async fn install(
         &mut self,
         cancel_channel: impl Into<Option<Receiver<()>>>,
+    ) -> Result<(), NixInstallerError> {
+        self.install_with_progress(cancel_channel, None).await
+    }
+
+    /// Like [`install`][InstallPlan::install], additionally sending an [`ActionProgress`] along `progress_channel` as each action starts and finishes
+    #[tracing::instrument(level = "debug", skip_all)]
+    pub async fn install_with_progress(
+        &mut self,
+        cancel_channel: impl Into<Option<Receiver<()>>>,
+        progress_channel: impl Into<Option<Sender<ActionProgress>>>,
     ) -> Result<(), NixInstallerError> {
         self.check_compatible()?;
         self.planner.pre_install_check().await?;
 
-        let Self { actions, .. } = self;
         let mut cancel_channel = cancel_channel.into();
+        let progress_channel = progress_channel.into();
 
         // This is **deliberately sequential**.
         // Actions which are parallelizable are represented by "group actions" like CreateUsers
         // The plan itself represents the concept of the sequence of stages.
-        for action in actions {
+        for idx in 0..self.actions.len() {
             if let Some(ref mut cancel_channel) = cancel_channel {
                 if cancel_channel.try_recv()
                     != Err(tokio::sync::broadcast::error::TryRecvError::Empty)
                 {
-                    if let Err(err) = write_receipt(self.clone()).await {
+                    if let Err(err) = write_receipt(self).await {
                         tracing::error!("Error saving receipt: {:?}", err);
                     }
 
@@ -188,9 +210,23 @@ impl InstallPlan {
                 }
             }
 
+            let action = &mut self.actions[idx];
             tracing::info!("Step: {}", action.tracing_synopsis());
+            let pending = !matches!(action.state, ActionState::Completed | ActionState::Skipped);
+            if pending {
+                send_progress(
+                    &progress_channel,
+                    ActionProgress::new(action, ActionState::Progress, None),
+                )
+                .await;
+            }
             if let Err(err) = action.try_execute().await {
-                if let Err(err) = write_receipt(self.clone()).await {
+                send_progress(
+                    &progress_channel,
+                    ActionProgress::new(action, action.state, format!("{err}")),
+                )
+                .await;
+                if let Err(err) = write_receipt(self).await {
                     tracing::error!("Error saving receipt: {:?}", err);
                 }
                 let err = NixInstallerError::Action(err);
@@ -208,9 +244,20 @@ impl InstallPlan {
 
                 return Err(err);
             }
+            send_progress(
+                &progress_channel,
+                ActionProgress::new(action, action.state, None),
+            )
+            .await;
+            // Persist progress as it is made, so a crash can resume from the receipt
+            if pending {
+                if let Err(err) = write_receipt(self).await {
+                    tracing::error!("Error saving receipt: {:?}", err);
+                }
+            }
         }
 
-        write_receipt(self.clone()).await?;
+        write_receipt(self).await?;
 
         if let Err(err) = crate::self_test::self_test()
             .await
@@ -245,6 +292,104 @@ impl InstallPlan {
         Ok(())
     }
 
+    /// Like [`install_with_progress`][InstallPlan::install_with_progress], but run on a separate task, returning a [`Stream`](futures_core::Stream) of the progress as it happens
+    ///
+    /// The stream ends once the install finishes, whether it succeeded or failed on an action. The returned task hands the plan back, with the updated state of each action, alongside the result of the install.
+    pub fn install_stream(
+        mut self,
+        cancel_channel: impl Into<Option<Receiver<()>>>,
+    ) -> (ActionProgressStream, InstallTask) {
+        let cancel_channel = cancel_channel.into();
+        let (tx, rx) = tokio::sync::mpsc::channel(PROGRESS_STREAM_BUFFER);
+        let handle = tokio::spawn(async move {
+            // The sender is dropped once this returns, ending the stream
+            let res = self.install_with_progress(cancel_channel, tx).await;
+            (self, res)
+        });
+        (ActionProgressStream(rx), handle)
+    }
+
+    /// Like [`install_with_progress`][InstallPlan::install_with_progress], but cancelled through a [`CancellationToken`]
+    ///
+    /// The token is checked between actions. On cancellation, the actions which already ran are reverted in reverse order before [`NixInstallerError::Cancelled`] is returned.
+    #[tracing::instrument(level = "debug", skip_all)]
+    pub async fn install_with_cancellation(
+        &mut self,
+        cancellation_token: CancellationToken,
+        progress_channel: impl Into<Option<Sender<ActionProgress>>>,
+    ) -> Result<(), NixInstallerError> {
+        if cancellation_token.is_cancelled() {
+            return Err(NixInstallerError::Cancelled);
+        }
+
+        let (tx, rx) = tokio::sync::broadcast::channel(1);
+        let forward = tokio::spawn({
+            let cancellation_token = cancellation_token.clone();
+            async move {
+                cancellation_token.cancelled().await;
+                let _ = tx.send(());
+                // A closed channel is also treated as cancelled, so it must stay open until aborted
+                std::future::pending::<()>().await
+            }
+        });
+        let res = self.install_with_progress(rx, progress_channel).await;
+        forward.abort();
+
+        match res {
+            Err(NixInstallerError::Cancelled) => {
+                let mut errors = vec![];
+                for action in self.actions.iter_mut().rev() {
+                    tracing::info!("Revert: {}", action.tracing_synopsis());
+                    if let Err(err) = action.try_revert().await {
+                        errors.push(err);
+                    }
+                }
+                if let Err(err) = write_receipt(self).await {
+                    tracing::error!("Error saving receipt: {:?}", err);
+                }
+                if errors.is_empty() {
+                    Err(NixInstallerError::Cancelled)
+                } else {
+                    Err(NixInstallerError::ActionRevert(errors))
+                }
+            },
+            res => res,
+        }
+    }
+
+    /// Like [`install_with_cancellation`][InstallPlan::install_with_cancellation], cancelled by the first `SIGINT` or `SIGTERM`
+    ///
+    /// The first signal stops any further actions from starting and reverts those which already ran. A second signal stops reverting
+    /// and returns [`NixInstallerError::Interrupted`] right away, callers usually exit on it (with status 130, like the shell would).
+    #[tracing::instrument(level = "debug", skip_all)]
+    pub async fn install_with_signals(
+        &mut self,
+        progress_channel: impl Into<Option<Sender<ActionProgress>>>,
+    ) -> Result<(), NixInstallerError> {
+        let cancellation_token = CancellationToken::new();
+        let signals = Signals::new().map_err(NixInstallerError::SignalHandler)?;
+        let mut watcher = cancel_on_signals(cancellation_token.clone(), signals);
+        let res = tokio::select! {
+            res = self.install_with_cancellation(cancellation_token, progress_channel) => res,
+            Ok(true) = &mut watcher => Err(NixInstallerError::Interrupted),
+        };
+        watcher.abort();
+        res
+    }
+
+    /// Log the side effects of each planned action, without performing them
+    #[tracing::instrument(level = "debug", skip_all)]
+    pub async fn dry_run(&self) -> Result<(), NixInstallerError> {
+        self.check_compatible()?;
+
+        for action in &self.actions {
+            tracing::info!("Step: {}", action.tracing_synopsis());
+            action.try_dry_run().await;
+        }
+
+        Ok(())
+    }
+
     #[tracing::instrument(level = "debug", skip_all)]
     pub async fn describe_uninstall(&self, explain: bool) -> Result<String, NixInstallerError> {
         let Self {
@@ -341,7 +486,7 @@ impl InstallPlan {
                 if cancel_channel.try_recv()
                     != Err(tokio::sync::broadcast::error::TryRecvError::Empty)
                 {
-                    if let Err(err) = write_receipt(self.clone()).await {
+                    if let Err(err) = write_receipt(self).await {
                         tracing::error!("Error saving receipt: {:?}", err);
                     }
 
@@ -396,6 +541,160 @@ impl InstallPlan {
         }
     }
 
+    /// Write the plan, including the [`ActionState`] of each action, as a JSON receipt at `path`
+    ///
+    /// The receipt can later be loaded with [`InstallPlan::from_receipt`] and passed to [`InstallPlan::uninstall`], without the original invocation arguments.
+    ///
+    /// The receipt is written to a sibling temporary file, then renamed over `path`, so a crash part way through never leaves a truncated receipt.
+    pub async fn write_receipt_to(&self, path: impl AsRef<Path>) -> Result<(), NixInstallerError> {
+        let path = path.as_ref();
+        let self_json =
+            serde_json::to_string_pretty(&self).map_err(NixInstallerError::SerializingReceipt)?;
+        let mut temp_name = std::ffi::OsString::from(".");
+        temp_name.push(path.file_name().unwrap_or_default());
+        temp_name.push(".tmp");
+        let temp_path = path.with_file_name(temp_name);
+        tokio::fs::write(&temp_path, format!("{self_json}\n"))
+            .await
+            .map_err(|e| NixInstallerError::RecordingReceipt(temp_path.clone(), e))?;
+        if let Err(e) = tokio::fs::rename(&temp_path, path).await {
+            if let Err(err) = tokio::fs::remove_file(&temp_path).await {
+                tracing::debug!("Could not remove `{}`: {err}", temp_path.display());
+            }
+            return Err(NixInstallerError::RecordingReceipt(path.to_path_buf(), e));
+        }
+        Ok(())
+    }
+
+    /// Load a plan previously written with [`InstallPlan::write_receipt_to`], such as [`RECEIPT_LOCATION`]
+    ///
+    /// Version compatibility is not checked here, see [`InstallPlan::check_compatible`].
+    pub async fn from_receipt(path: impl AsRef<Path>) -> Result<Self, NixInstallerError> {
+        let path = path.as_ref();
+        let receipt = tokio::fs::read_to_string(path)
+            .await
+            .map_err(|e| NixInstallerError::ReadingReceipt(path.to_path_buf(), e))?;
+        let plan: Self =
+            serde_json::from_str(&receipt).map_err(|e| match unsupported_action(&receipt) {
+                Some(action) => NixInstallerError::UnsupportedAction {
+                    path: path.to_path_buf(),
+                    action,
+                },
+                None => NixInstallerError::DeserializingReceipt(path.to_path_buf(), e),
+            })?;
+        // A receipt is only as trustworthy as the file it was read from
+        check_allowed_roots(&plan.actions, &allowed_roots(plan.planner.as_ref()))?;
+        Ok(plan)
+    }
+
+    /// Mark every action not matching `predicate` as [`Skipped`](ActionState::Skipped), so an install runs only the matching ones
+    ///
+    /// Intended for debugging a single action. Since the plan is sequential, each action skipped ahead of a matching one is a prerequisite
+    /// which will not be met, and is warned about. Returns how many actions will run.
+    pub fn retain_actions(
+        &mut self,
+        mut predicate: impl FnMut(&StatefulAction<Box<dyn Action>>) -> bool,
+    ) -> usize {
+        let mut unmet = vec![];
+        let mut selected = 0;
+        for action in self.actions.iter_mut() {
+            if matches!(action.state, ActionState::Completed | ActionState::Skipped) {
+                continue;
+            }
+            if predicate(action) {
+                selected += 1;
+                for prerequisite in unmet.drain(..) {
+                    tracing::warn!(
+                        "`{}` will run without its prerequisite `{prerequisite}`",
+                        action.tracing_synopsis()
+                    );
+                }
+            } else {
+                unmet.push(action.tracing_synopsis());
+                action.set_state(ActionState::Skipped);
+            }
+        }
+        selected
+    }
+
+    /// Like [`retain_actions`](InstallPlan::retain_actions), keeping the actions with the given [`action_id`](StatefulAction::action_id)s
+    pub fn retain_action_ids<S: AsRef<str>>(&mut self, ids: &[S]) -> usize {
+        self.retain_actions(|action| ids.iter().any(|id| id.as_ref() == action.action_id()))
+    }
+
+    /// The sum of the [`estimated_duration`](crate::action::Action::estimated_duration) of each action still to run, for showing an ETA
+    ///
+    /// Actions without an estimate are not counted, so this is advisory only.
+    pub fn estimated_duration(&self) -> Duration {
+        self.actions
+            .iter()
+            .filter_map(|action| action.estimated_duration())
+            .sum()
+    }
+
+    /// Every path the plan's actions read or write, and which actions do, for auditing and conflict analysis
+    ///
+    /// Built from [`paths_read`](crate::action::Action::paths_read) and [`paths_written`](crate::action::Action::paths_written), skipped actions touch nothing.
+    pub fn filesystem_footprint(&self) -> BTreeMap<PathBuf, PathFootprint> {
+        let mut footprint = BTreeMap::<PathBuf, PathFootprint>::new();
+        for action in &self.actions {
+            for path in action.paths_read() {
+                footprint
+                    .entry(path)
+                    .or_default()
+                    .read_by
+                    .push(action.tracing_synopsis());
+            }
+            for path in action.paths_written() {
+                footprint
+                    .entry(path)
+                    .or_default()
+                    .written_by
+                    .push(action.tracing_synopsis());
+            }
+        }
+        footprint
+    }
+
+    /// The index of the first action an install would run, after any already [`Completed`](ActionState::Completed) or [`Skipped`](ActionState::Skipped)
+    pub fn resume_point(&self) -> Option<usize> {
+        self.actions.iter().position(|action| {
+            !matches!(action.state, ActionState::Completed | ActionState::Skipped)
+        })
+    }
+
+    /// Load the receipt of a failed or cancelled install from `path` and [`install`][InstallPlan::install] it again
+    ///
+    /// Actions the receipt records as [`Completed`](ActionState::Completed) or [`Skipped`](ActionState::Skipped) are not re-run, so the install picks up at the [`resume_point`](InstallPlan::resume_point).
+    #[tracing::instrument(level = "debug", skip_all)]
+    pub async fn resume_from_receipt(
+        path: impl AsRef<Path>,
+        cancel_channel: impl Into<Option<Receiver<()>>>,
+    ) -> Result<Self, NixInstallerError> {
+        let mut plan = Self::from_receipt(path).await?;
+        match plan.resume_point() {
+            Some(idx) => tracing::info!(
+                "Resuming install at step {} of {}: {}",
+                idx + 1,
+                plan.actions.len(),
+                plan.actions[idx].tracing_synopsis()
+            ),
+            None => tracing::info!("Every step of the install is already complete"),
+        }
+        plan.install(cancel_channel).await?;
+        Ok(plan)
+    }
+
+    /// How long each executed action took, keyed by its [`tracing_synopsis`](crate::action::Action::tracing_synopsis)
+    ///
+    /// Actions which were skipped, found already complete, or not yet run are omitted. The timings are also recorded in the receipt.
+    pub fn action_timings(&self) -> Vec<(String, Duration)> {
+        self.actions
+            .iter()
+            .filter_map(|action| Some((action.tracing_synopsis(), action.elapsed()?)))
+            .collect()
+    }
+
     pub fn check_compatible(&self) -> Result<(), NixInstallerError> {
         let self_version_string = self.version.to_string();
         let req = VersionReq::parse(&self_version_string)
@@ -412,17 +711,403 @@ impl InstallPlan {
     }
 }
 
-async fn write_receipt(plan: InstallPlan) -> Result<(), NixInstallerError> {
-    tokio::fs::create_dir_all("/nix")
-        .await
-        .map_err(|e| NixInstallerError::RecordingReceipt(PathBuf::from("/nix"), e))?;
-    let install_receipt_path = PathBuf::from(RECEIPT_LOCATION);
-    let self_json =
-        serde_json::to_string_pretty(&plan).map_err(NixInstallerError::SerializingReceipt)?;
-    tokio::fs::write(&install_receipt_path, format!("{self_json}\n"))
-        .await
-        .map_err(|e| NixInstallerError::RecordingReceipt(install_receipt_path, e))?;
-    Result::<(), NixInstallerError>::Ok(())
+/// The task running an [`InstallPlan::install_stream`], resolving to the plan and the result of the install
+pub type InstallTask = tokio::task::JoinHandle<(InstallPlan, Result<(), NixInstallerError>)>;
+
+/// Each `SIGINT` or `SIGTERM` the process receives
+struct Signals {
+    interrupt: tokio::signal::unix::Signal,
+    terminate: tokio::signal::unix::Signal,
+}
+
+impl Signals {
+    fn new() -> Result<Self, std::io::Error> {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        // Registered before returning, so no signal sent after this is missed
+        Ok(Self {
+            interrupt: signal(SignalKind::interrupt())?,
+            terminate: signal(SignalKind::terminate())?,
+        })
+    }
+}
+
+impl futures_core::Stream for Signals {
+    type Item = ();
+
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        if self.interrupt.poll_recv(cx).is_ready() {
+            tracing::warn!("Got SIGINT signal");
+            return std::task::Poll::Ready(Some(()));
+        }
+        if self.terminate.poll_recv(cx).is_ready() {
+            tracing::warn!("Got SIGTERM signal");
+            return std::task::Poll::Ready(Some(()));
+        }
+        std::task::Poll::Pending
+    }
+}
+
+/// Cancel `cancellation_token` on the first of the `signals`, resolving to `true` on the second, or `false` if they end first
+fn cancel_on_signals(
+    cancellation_token: CancellationToken,
+    mut signals: impl futures_core::Stream<Item = ()> + Unpin + Send + 'static,
+) -> tokio::task::JoinHandle<bool> {
+    tokio::spawn(async move {
+        while std::future::poll_fn(|cx| std::pin::Pin::new(&mut signals).poll_next(cx))
+            .await
+            .is_some()
+        {
+            if cancellation_token.is_cancelled() {
+                tracing::error!("Got a second signal, stopping without finishing the revert");
+                return true;
+            }
+            tracing::warn!(
+                "Reverting the actions which already ran, send another signal to stop immediately"
+            );
+            cancellation_token.cancel();
+        }
+        false
+    })
+}
+
+/// How many [`ActionProgress`] events an [`ActionProgressStream`] buffers before the install waits for them to be consumed
+const PROGRESS_STREAM_BUFFER: usize = 32;
+
+/// The [`ActionProgress`] of an install started with [`InstallPlan::install_stream`]
+#[derive(Debug)]
+pub struct ActionProgressStream(tokio::sync::mpsc::Receiver<ActionProgress>);
+
+impl futures_core::Stream for ActionProgressStream {
+    type Item = ActionProgress;
+
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        self.0.poll_recv(cx)
+    }
+}
+
+async fn send_progress(
+    progress_channel: &Option<Sender<ActionProgress>>,
+    progress: ActionProgress,
+) {
+    if let Some(progress_channel) = progress_channel {
+        // A receiver going away should never interrupt an install
+        if progress_channel.send(progress).await.is_err() {
+            tracing::trace!("Progress receiver dropped, not sending progress");
+        }
+    }
+}
+
+/// Write the receipt to [`RECEIPT_LOCATION`], skipped until an action has created `/nix`
+///
+/// On some planners `/nix` is a mount or a directory on an immutable root which the plan
+/// itself sets up, so creating it early would fail or leave the receipt under the root filesystem.
+async fn write_receipt(plan: &InstallPlan) -> Result<(), NixInstallerError> {
+    if !Path::new("/nix").exists() {
+        tracing::debug!("Not saving receipt, `/nix` does not exist");
+        return Ok(());
+    }
+    plan.write_receipt_to(RECEIPT_LOCATION).await
+}
+
+/// The actions touching a path, see [`InstallPlan::filesystem_footprint`]
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct PathFootprint {
+    /// The synopses of the actions which read the path
+    pub read_by: Vec<String>,
+    /// The synopses of the actions which write, create, or remove the path
+    pub written_by: Vec<String>,
+}
+
+/// Just enough of a receipt to read the tag of each action, see [`unsupported_action`]
+#[derive(serde::Deserialize)]
+struct ReceiptTags {
+    actions: Vec<StatefulActionTag>,
+}
+
+#[derive(serde::Deserialize)]
+struct StatefulActionTag {
+    action: ActionTagOnly,
+}
+
+#[derive(serde::Deserialize)]
+struct ActionTagOnly {
+    action: String,
+}
+
+/// The tags of every [`Action`] registered in this build
+///
+/// `typetag` has no public registry, but lists every registered tag when asked for one it does not know.
+/// Empty if the error did not list them as expected.
+fn registered_action_tags() -> Vec<String> {
+    let Err(err) = serde_json::from_value::<Box<dyn Action>>(serde_json::json!({ "action": "" }))
+    else {
+        return vec![];
+    };
+    expected_variants(&err.to_string())
+}
+
+/// The variants listed by a serde unknown variant error, which are formatted differently when there is only one
+fn expected_variants(message: &str) -> Vec<String> {
+    let Some((_, expected)) = message.split_once("expected ") else {
+        return vec![];
+    };
+    // Anything following the list, such as serde_json's ` at line 1 column 2`
+    let expected = expected.split(" at line ").next().unwrap_or_default();
+    let expected = expected.strip_prefix("one of ").unwrap_or(expected);
+    expected
+        .split(", ")
+        .filter_map(|tag| tag.strip_prefix('`')?.strip_suffix('`'))
+        .map(ToString::to_string)
+        .collect()
+}
+
+/// The tag of the first action in `receipt` which this build has no such action for, such as one behind a disabled feature
+pub(crate) fn unsupported_action(receipt: &str) -> Option<String> {
+    let receipt: ReceiptTags = serde_json::from_str(receipt).ok()?;
+    let registered = registered_action_tags();
+    // Not knowing the registered tags, every action would look unsupported
+    if registered.is_empty() {
+        return None;
+    }
+    receipt
+        .actions
+        .into_iter()
+        .map(|stateful| stateful.action.action)
+        .find(|tag| !registered.contains(tag))
+}
+
+/// Ensure no two actions create the same file, which would otherwise fail (or silently overwrite) part way through an install
+pub fn check_conflicts(
+    actions: &[StatefulAction<Box<dyn Action>>],
+) -> Result<(), NixInstallerError> {
+    let mut creators: Vec<(PathBuf, Vec<String>)> = vec![];
+    for action in actions {
+        if action.state == ActionState::Skipped {
+            continue;
+        }
+        for path in action.action.created_files() {
+            match creators.iter_mut().find(|(existing, _)| *existing == path) {
+                Some((_, synopses)) => synopses.push(action.tracing_synopsis()),
+                None => creators.push((path, vec![action.tracing_synopsis()])),
+            }
+        }
+    }
+
+    match creators
+        .into_iter()
+        .find(|(_, synopses)| synopses.len() > 1)
+    {
+        Some((path, actions)) => Err(NixInstallerError::PlanConflict { path, actions }),
+        None => Ok(()),
+    }
+}
+
+/// An error from validating a planned [`InstallPlan`] before it is run
+#[non_exhaustive]
+#[derive(thiserror::Error, Debug, strum::IntoStaticStr)]
+pub enum PlanValidationError {
+    /// An action, or one of its sub-actions, writes a path outside every allowed root
+    #[error("`{action}` would write `{}`, which is outside the allowed roots {}, set `{ALLOWED_ROOTS_ENV}` to allow it", .path.display(), .roots.iter().map(|root| format!("`{}`", root.display())).collect::<Vec<_>>().join(", "))]
+    PathOutsideAllowedRoots {
+        path: PathBuf,
+        action: String,
+        roots: Vec<PathBuf>,
+    },
+}
+
+/// The directories actions may write in: `/nix`, `/etc` for profile snippets and services, the default Fish configuration directories, and `$HOME`
+///
+/// A `$HOME` of `/` is ignored, as it would allow anything. The file named by `$GITHUB_PATH`, which the installer adds Nix to in GitHub Actions, is also allowed.
+pub fn default_allowed_roots() -> Vec<PathBuf> {
+    let fish = crate::planner::FishShellProfileLocations::default();
+    let mut roots = vec![PathBuf::from("/nix"), PathBuf::from("/etc")];
+    roots.extend(fish.confd_prefixes);
+    roots.extend(fish.vendor_confd_prefixes);
+    if let Some(home) = std::env::var_os("HOME").map(PathBuf::from) {
+        if home.is_absolute() && home.parent().is_some() {
+            roots.push(home);
+        }
+    }
+    if let Some(github_path) = std::env::var_os("GITHUB_PATH").map(PathBuf::from) {
+        if github_path.is_absolute() {
+            roots.push(github_path);
+        }
+    }
+    // Tests plan into temporary directories
+    #[cfg(test)]
+    roots.push(std::env::temp_dir());
+    roots
+}
+
+/// The [`ALLOWED_ROOTS_ENV`] roots if set and not empty, otherwise the [`Planner::allowed_roots`]
+fn allowed_roots(planner: &dyn Planner) -> Vec<PathBuf> {
+    let roots = std::env::var_os(ALLOWED_ROOTS_ENV)
+        .map(|roots| {
+            std::env::split_paths(&roots)
+                .filter(|root| !root.as_os_str().is_empty())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    if roots.is_empty() {
+        planner.allowed_roots()
+    } else {
+        roots
+    }
+}
+
+/// Ensure every path the `actions` write only falls under one of `roots`
+///
+/// Actions which call sub-actions include theirs in their [`paths_written`](Action::paths_written), so those are checked too. Skipped actions write nothing.
+/// This guards against a buggy planner, or an unexpected `$HOME`, writing somewhere like `/`. Relative paths, and paths containing `..`, are never allowed.
+pub fn check_allowed_roots(
+    actions: &[StatefulAction<Box<dyn Action>>],
+    roots: &[PathBuf],
+) -> Result<(), NixInstallerError> {
+    for action in actions {
+        for path in action.paths_written() {
+            let is_normal = path.is_absolute()
+                && !path
+                    .components()
+                    .any(|component| component == std::path::Component::ParentDir);
+            if !(is_normal && roots.iter().any(|root| path.starts_with(root))) {
+                return Err(PlanValidationError::PathOutsideAllowedRoots {
+                    path,
+                    action: action.tracing_synopsis(),
+                    roots: roots.to_vec(),
+                }
+                .into());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Preview the actions which have yet to run, like `git diff`
+///
+/// Files an action writes (see [`Action::planned_contents`]) are rendered as unified diffs against their current content on disk. Other actions contribute the description of what they will do.
+pub fn plan_diff(actions: &[StatefulAction<Box<dyn Action>>]) -> String {
+    let mut buf = String::new();
+    for action in actions {
+        if matches!(action.state, ActionState::Completed | ActionState::Skipped) {
+            continue;
+        }
+        let planned_contents = action.action.planned_contents();
+        if planned_contents.is_empty() {
+            for description in action.describe_execute() {
+                buf.push_str(&format!("# {}\n", description.description));
+            }
+            continue;
+        }
+        for (path, new) in planned_contents {
+            let old = std::fs::read_to_string(&path).ok();
+            buf.push_str(&unified_diff(&path, old.as_deref(), &new));
+        }
+    }
+    buf
+}
+
+/// How many unchanged lines surround each hunk of a [`unified_diff`]
+const DIFF_CONTEXT: usize = 3;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum DiffOp<'a> {
+    Equal(&'a str),
+    Delete(&'a str),
+    Insert(&'a str),
+}
+
+/// A unified diff of `old` (or `/dev/null` if it does not exist) against `new`, empty if they are the same
+fn unified_diff(path: &Path, old: Option<&str>, new: &str) -> String {
+    let old_lines = old.unwrap_or_default().lines().collect::<Vec<_>>();
+    let new_lines = new.lines().collect::<Vec<_>>();
+    let (n, m) = (old_lines.len(), new_lines.len());
+
+    // `lcs[i][j]` is the length of the longest common subsequence of `old_lines[i..]` and `new_lines[j..]`
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if old_lines[i] == new_lines[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut ops = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && old_lines[i] == new_lines[j] {
+            ops.push(DiffOp::Equal(old_lines[i]));
+            i += 1;
+            j += 1;
+        } else if i < n && (j == m || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            ops.push(DiffOp::Delete(old_lines[i]));
+            i += 1;
+        } else {
+            ops.push(DiffOp::Insert(new_lines[j]));
+            j += 1;
+        }
+    }
+
+    // Group the changes, with their context, into non-overlapping hunks
+    let mut hunks: Vec<(usize, usize)> = vec![];
+    for (idx, op) in ops.iter().enumerate() {
+        if matches!(op, DiffOp::Equal(_)) {
+            continue;
+        }
+        let start = idx.saturating_sub(DIFF_CONTEXT);
+        let end = (idx + DIFF_CONTEXT + 1).min(ops.len());
+        match hunks.last_mut() {
+            Some((_, last_end)) if start <= *last_end => *last_end = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+    if hunks.is_empty() && old.is_some() {
+        return String::new();
+    }
+
+    let old_name = match old {
+        Some(_) => path.display().to_string(),
+        None => "/dev/null".to_string(),
+    };
+    let mut buf = format!("--- {old_name}\n+++ {}\n", path.display());
+    for (start, end) in hunks {
+        let before = &ops[..start];
+        let hunk = &ops[start..end];
+        let count = |ops: &[DiffOp], new: bool| {
+            ops.iter()
+                .filter(|op| match op {
+                    DiffOp::Equal(_) => true,
+                    DiffOp::Delete(_) => !new,
+                    DiffOp::Insert(_) => new,
+                })
+                .count()
+        };
+        let range = |new: bool| {
+            let (preceding, len) = (count(before, new), count(hunk, new));
+            // An empty range refers to the line before it
+            let start = if len == 0 { preceding } else { preceding + 1 };
+            format!("{start},{len}")
+        };
+        buf.push_str(&format!("@@ -{} +{} @@\n", range(false), range(true)));
+        for op in hunk {
+            let (prefix, line) = match op {
+                DiffOp::Equal(line) => (' ', line),
+                DiffOp::Delete(line) => ('-', line),
+                DiffOp::Insert(line) => ('+', line),
+            };
+            buf.push_str(&format!("{prefix}{line}\n"));
+        }
+    }
+    buf
 }
 
 pub fn current_version() -> Result<Version, NixInstallerError> {
@@ -434,9 +1119,36 @@ pub fn current_version() -> Result<Version, NixInstallerError> {
 
 #[cfg(test)]
 mod test {
+    use std::path::PathBuf;
+
     use semver::Version;
 
-    use crate::{planner::BuiltinPlanner, InstallPlan, NixInstallerError};
+    use super::PlanValidationError;
+
+    use crate::{
+        action::{base::CreateFile, Action, ActionState, StatefulAction},
+        planner::BuiltinPlanner,
+        InstallPlan, NixInstallerError,
+    };
+
+    /// A [`CreateFile`] of `path` with some placeholder content
+    async fn create_file(
+        path: impl AsRef<std::path::Path>,
+    ) -> eyre::Result<StatefulAction<CreateFile>> {
+        Ok(CreateFile::plan(path, None, None, None, "Test".into(), false).await?)
+    }
+
+    /// A receipt of `actions`, as planned by the default planner of this version
+    async fn receipt_value(
+        actions: Vec<StatefulAction<Box<dyn Action>>>,
+    ) -> eyre::Result<serde_json::Value> {
+        let planner = BuiltinPlanner::default().await?;
+        Ok(serde_json::json!({
+            "planner": planner.boxed(),
+            "version": Version::parse(env!("CARGO_PKG_VERSION"))?,
+            "actions": actions,
+        }))
+    }
 
     #[tokio::test]
     async fn ensure_version_allows_compatible() -> Result<(), NixInstallerError> {
@@ -465,4 +1177,285 @@ mod test {
         assert!(maybe_plan.check_compatible().is_err());
         Ok(())
     }
+
+    #[tokio::test]
+    async fn receipt_round_trips_action_state() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let test_file = temp_dir.path().join("receipt_round_trips_action_state");
+        let mut create_file = create_file(test_file).await?;
+        create_file.state = ActionState::Completed;
+
+        let plan: InstallPlan =
+            serde_json::from_value(receipt_value(vec![create_file.boxed()]).await?)?;
+
+        let receipt = temp_dir.path().join("receipt.json");
+        plan.write_receipt_to(&receipt).await?;
+        let loaded = InstallPlan::from_receipt(&receipt).await?;
+
+        assert_eq!(loaded.actions.len(), 1);
+        assert_eq!(loaded.actions[0].inner_typetag_name(), "create_file");
+        assert_eq!(loaded.actions[0].state, ActionState::Completed);
+        assert_eq!(
+            serde_json::to_value(&loaded.actions)?,
+            serde_json::to_value(&plan.actions)?
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn resumes_after_completed_actions() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let mut actions = vec![];
+        for (name, state) in [
+            ("done", ActionState::Completed),
+            ("skipped", ActionState::Skipped),
+            ("failed", ActionState::Progress),
+            ("pending", ActionState::Uncompleted),
+        ] {
+            let mut create_file = create_file(temp_dir.path().join(name)).await?;
+            create_file.state = state;
+            actions.push(create_file.boxed());
+        }
+
+        let plan: InstallPlan = serde_json::from_value(receipt_value(actions).await?)?;
+        let receipt = temp_dir.path().join("receipt.json");
+        plan.write_receipt_to(&receipt).await?;
+
+        // Rewriting the receipt replaces it whole, leaving no temporary file behind
+        plan.write_receipt_to(&receipt).await?;
+        assert_eq!(std::fs::read_dir(temp_dir.path())?.count(), 1);
+
+        let mut loaded = InstallPlan::from_receipt(&receipt).await?;
+        assert_eq!(loaded.resume_point(), Some(2));
+        // Only the two actions still to run are estimated
+        assert_eq!(
+            loaded.estimated_duration(),
+            std::time::Duration::from_millis(20)
+        );
+
+        let footprint = loaded.filesystem_footprint();
+        assert_eq!(footprint.len(), 3, "The skipped action touches nothing");
+        assert_eq!(footprint[&temp_dir.path().join("done")].written_by.len(), 1);
+
+        let pending = loaded.actions[3].action_id().to_string();
+        assert_eq!(loaded.retain_action_ids(&[pending]), 1);
+        assert_eq!(loaded.actions[2].state, ActionState::Skipped);
+        assert_eq!(loaded.actions[0].state, ActionState::Completed);
+        assert_eq!(loaded.resume_point(), Some(3));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reports_unsupported_action_in_receipt() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let create_file = create_file(temp_dir.path().join("file")).await?;
+        let value = receipt_value(vec![create_file.boxed()]).await?;
+        let receipt = temp_dir.path().join("receipt.json");
+        let contents = serde_json::to_string(&value)?
+            .replace(r#""action":"create_file""#, r#""action":"compiled_out""#);
+        tokio::fs::write(&receipt, contents).await?;
+
+        match InstallPlan::from_receipt(&receipt).await {
+            Err(NixInstallerError::UnsupportedAction { action, .. }) => {
+                assert_eq!(action, "compiled_out")
+            },
+            other => panic!("Expected an unsupported action, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn lists_registered_action_tags() {
+        let tags = super::registered_action_tags();
+        assert!(tags.contains(&"create_file".to_string()), "{tags:?}");
+        assert!(tags.contains(&"configure_nix".to_string()), "{tags:?}");
+        assert!(!tags.contains(&"compiled_out".to_string()));
+    }
+
+    #[test]
+    fn parses_expected_variants() {
+        assert_eq!(
+            super::expected_variants("unknown variant ``, expected one of `a`, `b`"),
+            ["a", "b"]
+        );
+        assert_eq!(
+            super::expected_variants("unknown variant ``, expected `a` at line 1 column 2"),
+            ["a"]
+        );
+        assert!(super::expected_variants("unknown variant ``, there are no variants").is_empty());
+    }
+
+    #[tokio::test]
+    async fn records_action_timings_in_receipt() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let test_file = temp_dir.path().join("records_action_timings_in_receipt");
+        let mut create_file = create_file(test_file).await?;
+        create_file.try_execute().await?;
+
+        let plan: InstallPlan =
+            serde_json::from_value(receipt_value(vec![create_file.boxed()]).await?)?;
+
+        let receipt = temp_dir.path().join("receipt.json");
+        plan.write_receipt_to(&receipt).await?;
+        let loaded = InstallPlan::from_receipt(&receipt).await?;
+
+        assert_eq!(loaded.action_timings().len(), 1);
+        assert_eq!(loaded.action_timings(), plan.action_timings());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn detects_conflicting_created_files() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let test_file = temp_dir.path().join("detects_conflicting_created_files");
+        let mut actions = vec![];
+        for buf in ["First", "Second"] {
+            actions.push(
+                CreateFile::plan(test_file.clone(), None, None, None, buf.into(), false)
+                    .await?
+                    .boxed(),
+            );
+        }
+
+        match super::check_conflicts(&actions) {
+            Err(NixInstallerError::PlanConflict { path, actions }) => {
+                assert_eq!(path, test_file);
+                assert_eq!(actions.len(), 2);
+            },
+            _ => return Err(eyre::eyre!("Should have returned a PlanConflict error")),
+        }
+        assert!(super::check_conflicts(&actions[..1]).is_ok());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rejects_paths_outside_allowed_roots() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let allowed = temp_dir.path().join("allowed");
+        let mut actions = vec![];
+        for path in [allowed.join("file"), allowed.join("../escaped")] {
+            actions.push(create_file(path).await?.boxed());
+        }
+
+        let roots = [allowed.clone()];
+        assert!(super::check_allowed_roots(&actions[..1], &roots).is_ok());
+        match super::check_allowed_roots(&actions, &roots) {
+            Err(NixInstallerError::PlanValidation(
+                PlanValidationError::PathOutsideAllowedRoots { path, .. },
+            )) => assert_eq!(path, allowed.join("../escaped")),
+            _ => return Err(eyre::eyre!("Should have rejected the escaping path")),
+        }
+        assert!(super::check_allowed_roots(&actions[..1], &[PathBuf::from("/nix")]).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rejects_nested_paths_outside_allowed_roots() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let locations = crate::planner::ShellProfileLocations {
+            fish: crate::planner::FishShellProfileLocations {
+                confd_prefixes: vec![],
+                vendor_confd_prefixes: vec![],
+                ..Default::default()
+            },
+            bash: vec![temp_dir.path().join("outside/bashrc")],
+            zsh: vec![],
+        };
+        let actions = vec![
+            crate::action::common::ConfigureShellProfile::plan(locations)
+                .await?
+                .boxed(),
+        ];
+
+        match super::check_allowed_roots(&actions, &[temp_dir.path().join("allowed")]) {
+            Err(NixInstallerError::PlanValidation(
+                PlanValidationError::PathOutsideAllowedRoots { path, .. },
+            )) => assert!(path.starts_with(temp_dir.path().join("outside"))),
+            _ => return Err(eyre::eyre!("Should have rejected the nested path")),
+        }
+        assert!(super::check_allowed_roots(&actions, &[temp_dir.path().to_path_buf()]).is_ok());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rejects_receipts_writing_outside_allowed_roots() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let create_file = create_file("/nix-installer-outside-allowed-roots/file").await?;
+        let value = receipt_value(vec![create_file.boxed()]).await?;
+        let receipt = temp_dir.path().join("receipt.json");
+        tokio::fs::write(&receipt, serde_json::to_string(&value)?).await?;
+
+        assert!(matches!(
+            InstallPlan::from_receipt(&receipt).await,
+            Err(NixInstallerError::PlanValidation(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn renders_unified_diffs() {
+        let path = std::path::Path::new("/etc/example");
+        assert_eq!(
+            super::unified_diff(path, Some("a\nb\nc\n"), "a\nB\nc\nd\n"),
+            "--- /etc/example\n+++ /etc/example\n@@ -1,3 +1,4 @@\n a\n-b\n+B\n c\n+d\n"
+        );
+        assert_eq!(
+            super::unified_diff(path, None, "new\n"),
+            "--- /dev/null\n+++ /etc/example\n@@ -0,0 +1,1 @@\n+new\n"
+        );
+        assert_eq!(super::unified_diff(path, Some("same\n"), "same\n"), "");
+    }
+
+    #[tokio::test]
+    async fn diffs_planned_files_against_disk() -> eyre::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let test_file = temp_dir.path().join("diffs_planned_files_against_disk");
+        tokio::fs::write(&test_file, "Old\n").await?;
+        let create_file =
+            CreateFile::plan(test_file.clone(), None, None, None, "New\n".into(), true).await?;
+
+        let diff = super::plan_diff(&[create_file.boxed()]);
+        assert!(diff.contains("-Old\n+New\n"), "{diff}");
+        Ok(())
+    }
+
+    /// Signals sent through a channel, instead of to the test process
+    struct ChannelSignals(tokio::sync::mpsc::UnboundedReceiver<()>);
+
+    impl futures_core::Stream for ChannelSignals {
+        type Item = ();
+
+        fn poll_next(
+            mut self: std::pin::Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<Option<Self::Item>> {
+            self.0.poll_recv(cx)
+        }
+    }
+
+    #[tokio::test]
+    async fn cancels_on_first_signal() -> eyre::Result<()> {
+        let timeout = std::time::Duration::from_secs(10);
+        let cancellation_token = tokio_util::sync::CancellationToken::new();
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        let mut watcher =
+            super::cancel_on_signals(cancellation_token.clone(), ChannelSignals(receiver));
+
+        sender.send(())?;
+        tokio::time::timeout(timeout, cancellation_token.cancelled()).await?;
+        assert!(!watcher.is_finished());
+
+        sender.send(())?;
+        assert!(tokio::time::timeout(timeout, &mut watcher).await??);
+
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        let watcher = super::cancel_on_signals(
+            tokio_util::sync::CancellationToken::new(),
+            ChannelSignals(receiver),
+        );
+        drop(sender);
+        assert!(!tokio::time::timeout(timeout, watcher).await??);
+        Ok(())
+    }
 }
@@ -155,6 +155,13 @@ pub trait Planner: std::fmt::Debug + Send + Sync + dyn_clone::DynClone {
         Ok(())
     }
 
+    /// The directories the planned actions may write in, see [`check_allowed_roots`](crate::plan::check_allowed_roots)
+    ///
+    /// Planners writing anywhere else, such as a persistence directory, should extend the [`default_allowed_roots`](crate::plan::default_allowed_roots).
+    fn allowed_roots(&self) -> Vec<PathBuf> {
+        crate::plan::default_allowed_roots()
+    }
+
     #[cfg(feature = "diagnostics")]
     async fn diagnostic_data(&self) -> Result<crate::diagnostics::DiagnosticData, PlannerError>;
 }
@@ -11,7 +11,10 @@ use crate::{
     settings::{InitSystem, InstallSettingsError},
     Action, BuiltinPlanner,
 };
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
 use super::{
     linux::{
@@ -156,7 +159,7 @@ impl Planner for Ostree {
             .fish
             .vendor_confd_prefixes
             .iter()
-            .position(|v| *v == PathBuf::from("/usr/share/fish/"))
+            .position(|v| *v == Path::new("/usr/share/fish/"))
         {
             shell_profile_locations
                 .fish
@@ -298,6 +301,12 @@ impl Planner for Ostree {
 
         Ok(())
     }
+
+    fn allowed_roots(&self) -> Vec<PathBuf> {
+        let mut roots = crate::plan::default_allowed_roots();
+        roots.push(self.persistence.clone());
+        roots
+    }
 }
 
 impl From<Ostree> for BuiltinPlanner {
@@ -96,7 +96,11 @@ To test on a specific build id of the Steam Deck:
 6. Safely turn off the VM!
 
 */
-use std::{collections::HashMap, path::PathBuf, process::Output};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::Output,
+};
 
 use tokio::process::Command;
 
@@ -302,7 +306,7 @@ impl Planner for SteamDeck {
             .fish
             .vendor_confd_prefixes
             .iter()
-            .position(|v| *v == PathBuf::from("/usr/share/fish/"))
+            .position(|v| *v == Path::new("/usr/share/fish/"))
         {
             shell_profile_locations
                 .fish
@@ -420,6 +424,12 @@ impl Planner for SteamDeck {
 
         Ok(())
     }
+
+    fn allowed_roots(&self) -> Vec<PathBuf> {
+        let mut roots = crate::plan::default_allowed_roots();
+        roots.push(self.persistence.clone());
+        roots
+    }
 }
 
 impl From<SteamDeck> for BuiltinPlanner {
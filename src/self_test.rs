@@ -1,29 +1,123 @@
-use std::{process::Output, time::SystemTime};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::Output,
+    time::Duration,
+};
 
-use tokio::process::Command;
+use tokio::{process::Command, task::JoinSet};
 use which::which;
 
+use crate::planner::FishShellProfileLocations;
+
+const PROFILE_NIX_FILE_SHELL: &str = "/nix/var/nix/profiles/default/etc/profile.d/nix-daemon.sh";
+const PROFILE_NIX_FILE_FISH: &str = "/nix/var/nix/profiles/default/etc/profile.d/nix-daemon.fish";
+const NIX_CONF: &str = "/etc/nix/nix.conf";
+/// Created by `nix-channel --update` for `root`, such as by [`UpdateChannels`](crate::action::common::UpdateChannels)
+const ROOT_CHANNELS_PROFILE: &str = "/nix/var/nix/profiles/per-user/root/channels";
+/// The socket `nix-daemon` listens on in multi-user installs
+pub const DAEMON_SOCKET: &str = "/nix/var/nix/daemon-socket/socket";
+/// Installed by [`ConfigureInitService`](crate::action::common::ConfigureInitService) on Linux
+const SYSTEMD_DAEMON_SOCKET_UNIT: &str = "/etc/systemd/system/nix-daemon.socket";
+/// Installed by [`ConfigureInitService`](crate::action::common::ConfigureInitService) on Mac
+const LAUNCHD_DAEMON_PLIST: &str = "/Library/LaunchDaemons/org.nixos.nix-daemon.plist";
+/// When set (to anything but `0` or `false`), [`self_test`] is skipped, for environments which cannot spawn shells
+pub const NO_SELF_TEST_ENV: &str = "NIX_INSTALLER_NO_SELF_TEST";
+
+/// How long a shell may take to complete its self-test before it is considered hung
+pub const DEFAULT_SELF_TEST_TIMEOUT: Duration = Duration::from_secs(10);
+
 #[non_exhaustive]
 #[derive(thiserror::Error, Debug, strum::IntoStaticStr)]
 pub enum SelfTestError {
-    #[error("Shell `{shell}` failed self-test with command `{command}`, stderr:\n{}", String::from_utf8_lossy(&output.stderr))]
+    #[error("Shell `{shell}` failed {mode} self-test with command `{}`, stderr:\n{}", shell_quote(.command), display_stderr(&output.stderr))]
     ShellFailed {
         shell: Shell,
-        command: String,
+        /// Which startup files were sourced, and so which need fixing
+        mode: SelfTestMode,
+        command: Vec<String>,
         output: Output,
     },
     /// Failed to execute command
-    #[error("Failed to execute command `{command}`",
-        command = .command,
-    )]
+    #[error("Shell `{shell}` failed to execute command `{}`", shell_quote(.command))]
     Command {
         shell: Shell,
-        command: String,
+        command: Vec<String>,
         #[source]
         error: std::io::Error,
     },
-    #[error(transparent)]
-    SystemTime(#[from] std::time::SystemTimeError),
+    /// The profile file the shell needs to source is missing
+    #[error("Shell `{shell}` could not find its Nix profile at `{}`, the install may be incomplete", .path.display())]
+    MissingProfile { shell: Shell, path: PathBuf },
+    /// Fish does not read the POSIX profile, and none of the `nix.fish` files it reads instead exist
+    #[error("Shell `fish` could not find `nix`, and none of its Nix integration files exist: {}", .searched.iter().map(|path| format!("`{}`", path.display())).collect::<Vec<_>>().join(", "))]
+    MissingFishIntegration { searched: Vec<PathBuf> },
+    /// The shell did not complete its self-test in time
+    #[error("Shell `{shell}` did not complete its self-test within {}s, it may be waiting on input while sourcing its profile", .elapsed.as_secs_f32())]
+    Timeout { shell: Shell, elapsed: Duration },
+    /// A channel install cannot resolve `<nixpkgs>`, from neither `$NIX_PATH` nor the `nix-path` setting
+    #[error("Shell `{shell}` cannot find `nixpkgs` on the Nix search path, so channels like `nixpkgs.hello` cannot be found, stderr:\n{}", display_stderr(&output.stderr))]
+    MissingNixPath { shell: Shell, output: Output },
+    /// The `nix-daemon` socket is missing or refused a connection
+    #[error("Could not connect to the Nix daemon at `{}`, `nix-daemon` may not be running", .path.display())]
+    DaemonUnreachable {
+        path: PathBuf,
+        #[source]
+        error: std::io::Error,
+    },
+    /// Neither `NIX_SSL_CERT_FILE` nor `SSL_CERT_FILE` point to an existing file, so `nix` cannot verify TLS connections
+    #[error("Shell `{shell}` {}, so `nix` will fail to download over TLS", match .path {
+        Some(path) => format!("has the CA certificate file `{}` set, but it does not exist", path.display()),
+        None => "has neither `NIX_SSL_CERT_FILE` nor `SSL_CERT_FILE` set".to_string(),
+    })]
+    MissingSslCertFile { shell: Shell, path: Option<PathBuf> },
+    /// An alias or function named `nix` in the shell's startup files hides the installed binary
+    #[error("Shell `{shell}` resolves `nix` to `{resolved}` instead of the installed binary, an alias or function named `nix` may be defined in its startup files")]
+    NixShadowed { shell: Shell, resolved: String },
+    /// A [`CustomCheck`] did not exit with the code it expected
+    #[error("Custom check `{name}` in shell `{shell}` exited with {} instead of {expected_exit_code}, stderr:\n{}", output.status.code().map(|code| format!("`{code}`")).unwrap_or_else(|| "a signal".to_string()), display_stderr(&output.stderr))]
+    CustomCheckFailed {
+        name: String,
+        shell: Shell,
+        expected_exit_code: i32,
+        output: Output,
+    },
+    /// Failed to join the task running the self-test of a shell
+    #[error("Joining self-test task for shell `{shell}`")]
+    Join {
+        shell: Shell,
+        #[source]
+        error: tokio::task::JoinError,
+    },
+}
+
+impl SelfTestError {
+    /// The stderr captured from a [`ShellFailed`](SelfTestError::ShellFailed) shell, exactly as it was written
+    pub fn stderr_raw(&self) -> Option<&[u8]> {
+        match self {
+            Self::ShellFailed { output, .. }
+            | Self::MissingNixPath { output, .. }
+            | Self::CustomCheckFailed { output, .. } => Some(&output.stderr),
+            _ => None,
+        }
+    }
+
+    /// Like [`stderr_raw`](SelfTestError::stderr_raw), with any invalid UTF-8 replaced by `�`
+    pub fn stderr_lossy(&self) -> Option<String> {
+        self.stderr_raw()
+            .map(|stderr| String::from_utf8_lossy(stderr).into_owned())
+    }
+}
+
+/// Render captured `stderr` for display, noting if bytes had to be replaced so users know to inspect the raw capture
+fn display_stderr(stderr: &[u8]) -> String {
+    match std::str::from_utf8(stderr) {
+        Ok(stderr) => stderr.to_string(),
+        Err(_) => format!(
+            "{}\n(stderr was not valid UTF-8, invalid bytes were replaced with `\u{FFFD}`)",
+            String::from_utf8_lossy(stderr)
+        ),
+    }
 }
 
 #[cfg(feature = "diagnostics")]
@@ -31,9 +125,18 @@ impl crate::diagnostics::ErrorDiagnostic for SelfTestError {
     fn diagnostic(&self) -> String {
         let static_str: &'static str = (self).into();
         let context = match self {
-            Self::ShellFailed { shell, .. } => vec![shell.to_string()],
+            Self::ShellFailed { shell, mode, .. } => vec![shell.to_string(), mode.to_string()],
             Self::Command { shell, .. } => vec![shell.to_string()],
-            Self::SystemTime(_) => vec![],
+            Self::MissingProfile { shell, .. } => vec![shell.to_string()],
+            Self::MissingFishIntegration { .. } => vec![Shell::Fish.to_string()],
+            Self::Timeout { shell, .. } => vec![shell.to_string()],
+            Self::MissingNixPath { shell, .. } => vec![shell.to_string()],
+            Self::DaemonUnreachable { .. } => vec![],
+            Self::MissingSslCertFile { shell, .. } => vec![shell.to_string()],
+            Self::NixShadowed { shell, .. } => vec![shell.to_string()],
+            // The name is chosen by the operator, and may be sensitive
+            Self::CustomCheckFailed { shell, .. } => vec![shell.to_string()],
+            Self::Join { shell, .. } => vec![shell.to_string()],
         };
         format!(
             "{}({})",
@@ -47,12 +150,34 @@ impl crate::diagnostics::ErrorDiagnostic for SelfTestError {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+/// How a shell is started for its self-test, which decides the startup files it sources
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SelfTestMode {
+    /// A login shell, sourcing system wide profiles like `/etc/profile`
+    Login,
+    /// An interactive non-login shell, sourcing per-shell startup files like `~/.bashrc`
+    Interactive,
+}
+
+impl std::fmt::Display for SelfTestMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SelfTestMode::Login => write!(f, "login"),
+            SelfTestMode::Interactive => write!(f, "interactive"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Shell {
     Sh,
     Bash,
     Fish,
     Zsh,
+    Nu,
+    Pwsh,
 }
 
 impl std::fmt::Display for Shell {
@@ -63,7 +188,14 @@ impl std::fmt::Display for Shell {
 
 impl Shell {
     pub fn all() -> &'static [Shell] {
-        &[Shell::Sh, Shell::Bash, Shell::Fish, Shell::Zsh]
+        &[
+            Shell::Sh,
+            Shell::Bash,
+            Shell::Fish,
+            Shell::Zsh,
+            Shell::Nu,
+            Shell::Pwsh,
+        ]
     }
     pub fn executable(&self) -> &'static str {
         match &self {
@@ -71,12 +203,442 @@ impl Shell {
             Shell::Bash => "bash",
             Shell::Fish => "fish",
             Shell::Zsh => "zsh",
+            Shell::Nu => "nu",
+            Shell::Pwsh => "pwsh",
+        }
+    }
+
+    /// Classify an arbitrary shell binary, such as `/usr/local/bin/bash`, by its file name
+    ///
+    /// If the file name is not recognized, symlinks are resolved and the target is classified instead.
+    pub fn from_path(path: &Path) -> Option<Shell> {
+        let from_file_name = |path: &Path| {
+            let file_name = path.file_name()?.to_str()?;
+            Self::all()
+                .iter()
+                .find(|shell| shell.executable() == file_name)
+                .copied()
+        };
+        from_file_name(path).or_else(|| from_file_name(&path.canonicalize().ok()?))
+    }
+
+    /// The `$SHELL` of the current user, if it points to a known shell
+    fn from_env() -> Option<(Shell, PathBuf)> {
+        let path = PathBuf::from(std::env::var_os("SHELL")?);
+        Self::from_path(&path).map(|shell| (shell, path))
+    }
+
+    /// The location of this shell, preferring `PATH` and falling back to `$SHELL`
+    pub fn path(&self) -> Option<PathBuf> {
+        if let Ok(path) = which(self.executable()) {
+            return Some(path);
+        }
+        match Self::from_env() {
+            Some((shell, path)) if shell == *self && path.exists() => Some(path),
+            _ => None,
         }
     }
 
+    /// The program to run for this shell, preferring `PATH` and falling back to `$SHELL`
+    fn program(&self) -> String {
+        if which(self.executable()).is_err() {
+            if let Some(path) = self.path() {
+                return path.display().to_string();
+            }
+        }
+        self.executable().to_string()
+    }
+
+    /// The first line of the shell's `--version` output, if it supports the flag
     #[tracing::instrument(skip_all)]
-    pub async fn self_test(&self) -> Result<(), SelfTestError> {
-        Ok(())
+    pub async fn version(&self) -> Option<String> {
+        let mut command = c_locale_command(self.program());
+        command
+            .arg("--version")
+            .stdin(std::process::Stdio::null())
+            .kill_on_drop(true);
+        let output = tokio::time::timeout(DEFAULT_SELF_TEST_TIMEOUT, command.output())
+            .await
+            .ok()?
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .map(ToString::to_string)
+    }
+
+    /// The flags required to make the shell source its login (and, where relevant, interactive) profile
+    pub fn login_flags(&self) -> &'static [&'static str] {
+        match &self {
+            Shell::Sh => &["-l"],
+            Shell::Bash | Shell::Zsh => &["-l", "-i"],
+            Shell::Fish | Shell::Nu => &["-l"],
+            // The user's own profile may prompt or print, and cannot source the Nix profile anyway
+            Shell::Pwsh => &["-NoProfile"],
+        }
+    }
+
+    /// The flags required to make the shell start as an interactive non-login shell
+    pub fn interactive_flags(&self) -> &'static [&'static str] {
+        match &self {
+            Shell::Sh | Shell::Bash | Shell::Zsh | Shell::Fish | Shell::Nu => &["-i"],
+            // PowerShell is always interactive, leaving out `-NoProfile` makes it source the user's profile
+            Shell::Pwsh => &[],
+        }
+    }
+
+    /// Whether an interactive non-login shell sources any startup file the installer configures
+    ///
+    /// `sh -i` only sources `$ENV`, and bash on macOS does not read `/etc/bashrc` unless it is a login shell.
+    pub fn sources_interactive_profile(&self) -> bool {
+        match &self {
+            Shell::Sh => false,
+            Shell::Bash => !cfg!(target_os = "macos"),
+            Shell::Zsh | Shell::Fish | Shell::Nu | Shell::Pwsh => true,
+        }
+    }
+
+    /// The Nix profile file the shell is expected to source
+    pub fn profile_file(&self) -> &'static Path {
+        match &self {
+            Shell::Sh | Shell::Bash | Shell::Zsh | Shell::Nu | Shell::Pwsh => {
+                Path::new(PROFILE_NIX_FILE_SHELL)
+            },
+            Shell::Fish => Path::new(PROFILE_NIX_FILE_FISH),
+        }
+    }
+
+    /// The per-user startup file of the shell, such as `~/.bashrc`, if `$HOME` is set
+    ///
+    /// PowerShell does not follow the conventions of the other shells, and keeps its profile under `~/.config/powershell`.
+    pub fn user_profile_file(&self) -> Option<PathBuf> {
+        let home = PathBuf::from(std::env::var_os("HOME")?);
+        let config_home = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| home.join(".config"));
+        Some(match &self {
+            Shell::Sh => home.join(".profile"),
+            Shell::Bash => home.join(".bashrc"),
+            Shell::Zsh => std::env::var_os("ZDOTDIR")
+                .map(PathBuf::from)
+                .unwrap_or(home)
+                .join(".zshrc"),
+            Shell::Fish => config_home.join("fish/config.fish"),
+            Shell::Nu => config_home.join("nushell/config.nu"),
+            Shell::Pwsh => config_home.join("powershell/Microsoft.PowerShell_profile.ps1"),
+        })
+    }
+
+    /// The full argument vector (including the executable) run by [`Shell::self_test`]
+    ///
+    /// If `flakes` is set, the new `nix` CLI is verified instead of `nix-env`.
+    pub fn self_test_command(&self, mode: SelfTestMode, flakes: bool) -> Vec<String> {
+        let nix_command = if flakes {
+            r#"nix --extra-experimental-features "nix-command flakes" flake --version"#
+        } else {
+            "nix-env --version"
+        };
+        self.command_with_nix_profile(mode, nix_command)
+    }
+
+    /// The full argument vector (including the executable) run by [`Shell::nix_path_self_test`]
+    pub fn nix_path_command(&self) -> Vec<String> {
+        self.command_with_nix_profile(SelfTestMode::Login, "nix-instantiate --find-file nixpkgs")
+    }
+
+    /// The full argument vector (including the executable) run by [`Shell::ssl_cert_self_test`]
+    pub fn ssl_cert_command(&self) -> Vec<String> {
+        self.command_with_nix_profile(SelfTestMode::Login, "env")
+    }
+
+    /// The full argument vector (including the executable) run by [`Shell::shadowing_self_test`], if the shell supports it
+    pub fn nix_resolution_command(&self) -> Option<Vec<String>> {
+        let script = match &self {
+            // Prints a path for a binary, but the definition or bare name for an alias or function
+            Shell::Sh | Shell::Bash | Shell::Zsh => "command -v nix",
+            Shell::Fish => "type -t nix",
+            // Neither reads user aliases while being tested, see [`Shell::login_flags`] and [`Shell::command_with_nix_profile`]
+            Shell::Nu | Shell::Pwsh => return None,
+        };
+        Some(self.script_with_nix_profile(SelfTestMode::Login, script))
+    }
+
+    /// The full argument vector (including the executable) run by [`Shell::login_nix`]
+    ///
+    /// Unlike [`Shell::self_test_command`] the Nix profile is not sourced on the shell's behalf, so only its own login startup files apply.
+    pub fn login_path_command(&self) -> Vec<String> {
+        let mut argv = vec![self.program()];
+        argv.extend(self.login_flags().iter().map(|flag| flag.to_string()));
+        let (flag, command) = match &self {
+            Shell::Pwsh => ("-Command", "printenv PATH; exit $LASTEXITCODE"),
+            Shell::Nu => ("-c", "^printenv PATH"),
+            _ => ("-c", "exec printenv PATH"),
+        };
+        argv.push(flag.to_string());
+        argv.push(command.to_string());
+        argv
+    }
+
+    /// Where `nix` is on the `PATH` of a login shell, rather than the `PATH` of this process
+    ///
+    /// A shell may be present while its Nix integration only loads at login, so this verifies what users get after install.
+    #[tracing::instrument(skip_all)]
+    pub async fn login_nix(&self, timeout: Duration) -> Result<Option<PathBuf>, SelfTestError> {
+        let argv = self.login_path_command();
+        let output = self.run(&argv, timeout).await?;
+        if !output.status.success() {
+            return Err(SelfTestError::ShellFailed {
+                shell: *self,
+                mode: SelfTestMode::Login,
+                command: argv,
+                output,
+            });
+        }
+        let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        Ok(which::which_in("nix", Some(path), "/").ok())
+    }
+
+    /// An argument vector running the `nix_command` binary in this shell, see [`Shell::script_with_nix_profile`] for how the Nix profile is loaded
+    fn command_with_nix_profile(&self, mode: SelfTestMode, nix_command: &str) -> Vec<String> {
+        let script = match &self {
+            Shell::Nu => format!("^{nix_command}"),
+            Shell::Pwsh => nix_command.to_string(),
+            // Distinguish `nix` missing from `PATH` from `nix` failing
+            Shell::Fish => {
+                let program = nix_command.split_whitespace().next().unwrap_or_default();
+                format!("type -q {program}; and exec {nix_command}")
+            },
+            _ => format!("exec {nix_command}"),
+        };
+        self.script_with_nix_profile(mode, &script)
+    }
+
+    /// An argument vector running `script`, written in this shell's own language, started in `mode`
+    ///
+    /// `sh`, `bash`, `zsh`, and `fish` are expected to load the Nix profile from their own startup files, as selected by `mode`'s flags, so nothing is sourced on their behalf.
+    /// Only `nu` and `pwsh`, which cannot source the POSIX profile, have its variables copied in explicitly before `script` runs.
+    fn script_with_nix_profile(&self, mode: SelfTestMode, script: &str) -> Vec<String> {
+        let mut argv = vec![self.program()];
+        let flags = match mode {
+            SelfTestMode::Login => self.login_flags(),
+            SelfTestMode::Interactive => self.interactive_flags(),
+        };
+        argv.extend(flags.iter().map(|flag| flag.to_string()));
+        argv.push(match &self {
+            Shell::Pwsh => "-Command".to_string(),
+            _ => "-c".to_string(),
+        });
+        argv.push(match &self {
+            // Nushell cannot source the POSIX profile, so lift the relevant variables out of `sh` instead
+            Shell::Nu => format!(
+                r#"load-env (^sh -c ". '{PROFILE_NIX_FILE_SHELL}' && env" | lines | parse "{{name}}={{value}}" | where name == "PATH" or name =~ "^NIX_" | transpose -r -d); {script}"#
+            ),
+            // PowerShell, likewise, so copy them into its environment before running `nix`
+            Shell::Pwsh => format!(
+                r#"sh -c ". '{PROFILE_NIX_FILE_SHELL}' && env" | ForEach-Object {{ $name, $value = $_ -split '=', 2; if ($name -eq 'PATH' -or $name -like 'NIX_*') {{ Set-Item -Path "Env:$name" -Value $value }} }}; {script}; exit $LASTEXITCODE"#
+            ),
+            _ => script.to_string(),
+        });
+        argv
+    }
+
+    /// The `nix.fish` files under each `conf.d` and `vendor_conf.d` which Fish sources at startup
+    fn fish_integration_files() -> Vec<PathBuf> {
+        let locations = FishShellProfileLocations::default();
+        locations
+            .confd_prefixes
+            .iter()
+            .map(|prefix| prefix.join(&locations.confd_suffix))
+            .chain(
+                locations
+                    .vendor_confd_prefixes
+                    .iter()
+                    .map(|prefix| prefix.join(&locations.vendor_confd_suffix)),
+            )
+            .collect()
+    }
+
+    /// Verify `nix` is reachable from a login shell
+    #[tracing::instrument(skip_all)]
+    pub async fn self_test(&self, timeout: Duration, flakes: bool) -> Result<(), SelfTestError> {
+        self.self_test_in(SelfTestMode::Login, timeout, flakes)
+            .await
+    }
+
+    /// Like [`Shell::self_test`], but from an interactive non-login shell, which sources different startup files
+    ///
+    /// A passing [`Shell::self_test`] can hide a broken interactive configuration, such as a `~/.bashrc` resetting `PATH`.
+    /// Shells which do not [source an interactive profile](Shell::sources_interactive_profile) are not tested.
+    #[tracing::instrument(skip_all)]
+    pub async fn interactive_self_test(
+        &self,
+        timeout: Duration,
+        flakes: bool,
+    ) -> Result<(), SelfTestError> {
+        if !self.sources_interactive_profile() {
+            tracing::debug!(
+                "Skipping interactive self-test of `{self}`, it sources no profile when interactive"
+            );
+            return Ok(());
+        }
+        self.self_test_in(SelfTestMode::Interactive, timeout, flakes)
+            .await
+    }
+
+    /// Verify `nixpkgs` resolves on the Nix search path in a login shell, as channel installs need for commands like `nix-env -iA nixpkgs.hello`
+    ///
+    /// The search path is resolved by `nix-instantiate` itself, so both `$NIX_PATH` and the `nix-path` setting (including its defaults) apply.
+    #[tracing::instrument(skip_all)]
+    pub async fn nix_path_self_test(&self, timeout: Duration) -> Result<(), SelfTestError> {
+        let argv = self.nix_path_command();
+        let output = self.run(&argv, timeout).await?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(SelfTestError::MissingNixPath {
+                shell: *self,
+                output,
+            })
+        }
+    }
+
+    /// Verify the CA certificate file `nix` uses for TLS is set in a login shell, and exists
+    ///
+    /// Like `nix`, `NIX_SSL_CERT_FILE` is preferred over `SSL_CERT_FILE`.
+    #[tracing::instrument(skip_all)]
+    pub async fn ssl_cert_self_test(&self, timeout: Duration) -> Result<(), SelfTestError> {
+        let argv = self.ssl_cert_command();
+        let output = self.run(&argv, timeout).await?;
+        if !output.status.success() {
+            return Err(SelfTestError::ShellFailed {
+                shell: *self,
+                mode: SelfTestMode::Login,
+                command: argv,
+                output,
+            });
+        }
+        let env = String::from_utf8_lossy(&output.stdout);
+        let path = ssl_cert_file_from_env(&env);
+        match path {
+            Some(path) if path.is_file() => Ok(()),
+            path => Err(SelfTestError::MissingSslCertFile { shell: *self, path }),
+        }
+    }
+
+    /// Verify `nix` resolves to a binary in a login shell, rather than an alias or function which may pass [`Shell::self_test`] while breaking other uses
+    #[tracing::instrument(skip_all)]
+    pub async fn shadowing_self_test(&self, timeout: Duration) -> Result<(), SelfTestError> {
+        let Some(argv) = self.nix_resolution_command() else {
+            return Ok(());
+        };
+        let output = self.run(&argv, timeout).await?;
+        if !output.status.success() {
+            return Err(SelfTestError::ShellFailed {
+                shell: *self,
+                mode: SelfTestMode::Login,
+                command: argv,
+                output,
+            });
+        }
+        match shadowed_nix(*self, &String::from_utf8_lossy(&output.stdout)) {
+            Some(resolved) => Err(SelfTestError::NixShadowed {
+                shell: *self,
+                resolved,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Run a [`CustomCheck`] in a login shell, set up like [`Shell::self_test`]
+    #[tracing::instrument(skip_all, fields(name = check.name))]
+    pub async fn custom_check(
+        &self,
+        check: &CustomCheck,
+        timeout: Duration,
+    ) -> Result<(), SelfTestError> {
+        let argv = self.script_with_nix_profile(SelfTestMode::Login, &check.script);
+        tracing::debug!(
+            command = shell_quote(&argv),
+            "Running custom check `{}` via `{self}`",
+            check.name
+        );
+        let output = self.run(&argv, timeout).await?;
+        if output.status.code() == Some(check.expected_exit_code) {
+            Ok(())
+        } else {
+            Err(SelfTestError::CustomCheckFailed {
+                name: check.name.clone(),
+                shell: *self,
+                expected_exit_code: check.expected_exit_code,
+                output,
+            })
+        }
+    }
+
+    /// Run `argv` with no input, killing it if it takes longer than `timeout`
+    async fn run(&self, argv: &[String], timeout: Duration) -> Result<Output, SelfTestError> {
+        let mut command = c_locale_command(&argv[0]);
+        command
+            .args(&argv[1..])
+            .stdin(std::process::Stdio::null())
+            .kill_on_drop(true);
+        tokio::time::timeout(timeout, command.output())
+            .await
+            .map_err(|_| SelfTestError::Timeout {
+                shell: *self,
+                elapsed: timeout,
+            })?
+            .map_err(|error| SelfTestError::Command {
+                shell: *self,
+                command: argv.to_vec(),
+                error,
+            })
+    }
+
+    async fn self_test_in(
+        &self,
+        mode: SelfTestMode,
+        timeout: Duration,
+        flakes: bool,
+    ) -> Result<(), SelfTestError> {
+        let executable = self.executable();
+        let profile_file = self.profile_file();
+        if !profile_file.exists() {
+            return Err(SelfTestError::MissingProfile {
+                shell: *self,
+                path: profile_file.to_path_buf(),
+            });
+        }
+
+        let argv = self.self_test_command(mode, flakes);
+        tracing::debug!(
+            command = shell_quote(&argv),
+            "Testing Nix install via {mode} `{executable}`"
+        );
+        let output = self.run(&argv, timeout).await?;
+
+        if output.status.success() {
+            Ok(())
+        } else if *self == Shell::Fish
+            && !Self::fish_integration_files()
+                .iter()
+                .any(|path| path.exists())
+        {
+            Err(SelfTestError::MissingFishIntegration {
+                searched: Self::fish_integration_files(),
+            })
+        } else {
+            Err(SelfTestError::ShellFailed {
+                shell: *self,
+                mode,
+                command: argv,
+                output,
+            })
+        }
     }
 
     #[tracing::instrument(skip_all)]
@@ -88,26 +650,732 @@ impl Shell {
                 found_shells.push(*shell)
             }
         }
+        if let Some((shell, path)) = Self::from_env() {
+            if !found_shells.contains(&shell) && path.exists() {
+                tracing::debug!("Discovered `{shell}` via `$SHELL` at `{}`", path.display());
+                found_shells.push(shell)
+            }
+        }
         found_shells
     }
+
+    /// Like [`Shell::discover`], but also reports where each shell is and which version it is
+    #[tracing::instrument(skip_all)]
+    pub async fn discover_with_versions() -> Vec<DiscoveredShell> {
+        let mut discovered = vec![];
+        for shell in Self::discover() {
+            discovered.push(DiscoveredShell {
+                shell,
+                path: shell.path(),
+                version: shell.version().await,
+                login_nix: None,
+            });
+        }
+        discovered
+    }
+
+    /// Like [`Shell::discover_with_versions`], but also probes where each shell finds `nix` via [`Shell::login_nix`]
+    #[tracing::instrument(skip_all)]
+    pub async fn discover_with_login_nix(timeout: Duration) -> Vec<DiscoveredShell> {
+        let mut discovered = Self::discover_with_versions().await;
+        for found in &mut discovered {
+            found.login_nix = match found.shell.login_nix(timeout).await {
+                Ok(login_nix) => login_nix,
+                Err(err) => {
+                    tracing::debug!(
+                        "Could not probe the login `PATH` of `{}`: {err}",
+                        found.shell
+                    );
+                    None
+                },
+            };
+        }
+        discovered
+    }
+}
+
+/// A shell found by [`Shell::discover_with_versions`]
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct DiscoveredShell {
+    pub shell: Shell,
+    pub path: Option<PathBuf>,
+    /// The first line of `--version`, `None` for shells like `dash` which do not support it
+    pub version: Option<String>,
+    /// Where `nix` is on the login `PATH` of the shell, only probed by [`Shell::discover_with_login_nix`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub login_nix: Option<PathBuf>,
+}
+
+/// What `nix` resolves to instead of a binary, given the output of [`Shell::nix_resolution_command`]
+fn shadowed_nix(shell: Shell, stdout: &str) -> Option<String> {
+    // Interactive startup files may print before the command runs
+    let resolved = stdout
+        .lines()
+        .map(str::trim)
+        .rfind(|line| !line.is_empty())?;
+    let is_binary = match shell {
+        Shell::Fish => resolved == "file",
+        _ => resolved.starts_with('/'),
+    };
+    (!is_binary).then(|| resolved.to_string())
 }
 
+/// The CA certificate file `nix` would use, given the output of `env`
+fn ssl_cert_file_from_env(env: &str) -> Option<PathBuf> {
+    let find = |name: &str| {
+        env.lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix('='))
+            .filter(|value| !value.is_empty())
+            .map(PathBuf::from)
+    };
+    find("NIX_SSL_CERT_FILE").or_else(|| find("SSL_CERT_FILE"))
+}
+
+/// A [`Command`] running `program` in the `C` locale, so output like `nix --version` and error messages are not translated
+fn c_locale_command(program: impl AsRef<std::ffi::OsStr>) -> Command {
+    let mut command = Command::new(program);
+    command
+        .env("LC_ALL", "C")
+        .env("LANG", "C")
+        // GNU gettext prefers this over `LC_ALL`
+        .env_remove("LANGUAGE");
+    command
+}
+
+/// Render `argv` so it can be copy-pasted into a POSIX shell to reproduce the invocation
+fn shell_quote(argv: &[String]) -> String {
+    argv.iter()
+        .map(|arg| {
+            let is_safe = !arg.is_empty()
+                && arg
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c));
+            if is_safe {
+                arg.clone()
+            } else {
+                format!("'{}'", arg.replace('\'', r#"'\''"#))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// An operator's own smoke test, run by [`self_test_report_with_checks`] alongside the built-in ones
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct CustomCheck {
+    /// Identifies the check in errors and reports
+    pub name: String,
+    /// The shell the check is written for and runs in
+    pub shell: Shell,
+    /// Run by a login shell with `-c` (or `-Command`), set up like [`Shell::self_test`]
+    pub script: String,
+    pub expected_exit_code: i32,
+}
+
+impl CustomCheck {
+    /// A check which passes if `script` exits successfully
+    pub fn new(name: impl Into<String>, shell: Shell, script: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            shell,
+            script: script.into(),
+            expected_exit_code: 0,
+        }
+    }
+
+    pub fn expected_exit_code(mut self, expected_exit_code: i32) -> Self {
+        self.expected_exit_code = expected_exit_code;
+        self
+    }
+}
+
+/// The outcome of a [`CustomCheck`], suitable for serializing for other tools
+#[derive(Debug, serde::Serialize)]
+pub struct CustomCheckReport {
+    pub name: String,
+    pub shell: Shell,
+    pub passed: bool,
+    /// A human readable description of the failure
+    pub error: Option<String>,
+    #[serde(skip)]
+    failure: Option<SelfTestError>,
+}
+
+/// The outcome of a [`Shell::self_test`], suitable for serializing for other tools
+#[derive(Debug, serde::Serialize)]
+pub struct ShellReport {
+    pub shell: Shell,
+    pub passed: bool,
+    /// The exit code of the shell, if it ran to completion and failed
+    pub exit_code: Option<i32>,
+    /// The captured stderr of the shell, if it ran to completion and failed, with any invalid UTF-8 replaced
+    pub stderr: Option<String>,
+    /// The captured stderr as raw bytes, only if it was not valid UTF-8
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stderr_raw: Option<Vec<u8>>,
+    /// A human readable description of the failure
+    pub error: Option<String>,
+    #[serde(skip)]
+    failure: Option<SelfTestError>,
+}
+
+impl ShellReport {
+    fn new(shell: Shell, result: Result<(), SelfTestError>) -> Self {
+        let (exit_code, stderr, stderr_raw) = match &result {
+            Err(SelfTestError::ShellFailed { output, .. }) => (
+                output.status.code(),
+                Some(String::from_utf8_lossy(&output.stderr).into_owned()),
+                std::str::from_utf8(&output.stderr)
+                    .is_err()
+                    .then(|| output.stderr.clone()),
+            ),
+            _ => (None, None, None),
+        };
+        Self {
+            shell,
+            passed: result.is_ok(),
+            exit_code,
+            stderr,
+            stderr_raw,
+            error: result.as_ref().err().map(|err| err.to_string()),
+            failure: result.err(),
+        }
+    }
+}
+
+/// The outcome of a [`self_test_report`], in the order shells were discovered
+#[derive(Debug, serde::Serialize)]
+pub struct SelfTestReport {
+    pub shells: Vec<ShellReport>,
+    /// A human readable description of why [`daemon_self_test`] failed, if it was run and failed
+    pub daemon_error: Option<String>,
+    #[serde(skip)]
+    daemon_failure: Option<SelfTestError>,
+    /// Whether the [`daemon_self_test`] was run, as it is only if [`daemon_installed`]
+    pub daemon_tested: bool,
+    /// The outcomes of any [`CustomCheck`]s, in the order they were given
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub custom_checks: Vec<CustomCheckReport>,
+}
+
+impl SelfTestReport {
+    /// The number of shells which were tested
+    pub fn tested(&self) -> usize {
+        self.shells.len()
+    }
+
+    /// If no shells were found to test, in which case the report trivially [`passed`](Self::passed)
+    pub fn is_empty(&self) -> bool {
+        self.shells.is_empty()
+    }
+
+    pub fn passed(&self) -> bool {
+        self.daemon_failure.is_none()
+            && self.shells.iter().all(|report| report.passed)
+            && self.custom_checks.iter().all(|report| report.passed)
+    }
+
+    /// Count the checks which passed and failed, including the daemon and any custom checks, and the shells which were not tested
+    pub fn summary(&self) -> SelfTestSummary {
+        let outcomes = self
+            .shells
+            .iter()
+            .map(|report| report.passed)
+            .chain(self.daemon_tested.then_some(self.daemon_failure.is_none()))
+            .chain(self.custom_checks.iter().map(|report| report.passed))
+            .collect::<Vec<_>>();
+        let passed = outcomes.iter().filter(|passed| **passed).count();
+        SelfTestSummary {
+            tested: outcomes.len(),
+            passed,
+            failed: outcomes.len() - passed,
+            skipped: Shell::all()
+                .iter()
+                .filter(|shell| !self.shells.iter().any(|report| report.shell == **shell))
+                .count(),
+        }
+    }
+
+    pub fn into_result(self) -> Result<(), Vec<SelfTestError>> {
+        let failures = self
+            .daemon_failure
+            .into_iter()
+            .chain(self.shells.into_iter().filter_map(|report| report.failure))
+            .chain(
+                self.custom_checks
+                    .into_iter()
+                    .filter_map(|report| report.failure),
+            )
+            .collect::<Vec<_>>();
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(failures)
+        }
+    }
+}
+
+/// Counts of the checks a self-test ran, see [`self_test_summarized`]
+///
+/// Each tested shell, the [`daemon_self_test`] (if run), and each [`CustomCheck`] counts as one check.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub struct SelfTestSummary {
+    pub tested: usize,
+    pub passed: usize,
+    pub failed: usize,
+    /// Shells which were not tested, because they were not discovered or [`NO_SELF_TEST_ENV`] is set
+    pub skipped: usize,
+}
+
+impl std::fmt::Display for SelfTestSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{} checks passed", self.passed, self.tested)?;
+        if self.skipped != 0 {
+            write!(f, ", {} shells skipped", self.skipped)?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether `/etc/nix/nix.conf` enables flakes, in which case the self-test verifies the `nix` CLI
+pub fn flakes_enabled() -> bool {
+    let Ok(nix_config) = nix_config_parser::NixConfig::parse_file(Path::new(NIX_CONF)) else {
+        return false;
+    };
+    let settings = nix_config.settings();
+    ["experimental-features", "extra-experimental-features"]
+        .iter()
+        .filter_map(|setting| settings.get(*setting))
+        .any(|features| {
+            features
+                .split_whitespace()
+                .any(|feature| feature == "flakes")
+        })
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn self_test_report() -> SelfTestReport {
+    self_test_report_with(
+        &Shell::discover(),
+        flakes_enabled(),
+        DEFAULT_SELF_TEST_TIMEOUT,
+    )
+    .await
+}
+
+/// If `root` has channels, as a channel based install does
+pub fn channels_installed() -> bool {
+    Path::new(ROOT_CHANNELS_PROFILE).exists()
+}
+
+/// If a `nix-daemon` init service is installed, as a multi-user install does
+pub fn daemon_installed() -> bool {
+    Path::new(SYSTEMD_DAEMON_SOCKET_UNIT).exists() || Path::new(LAUNCHD_DAEMON_PLIST).exists()
+}
+
+/// Check the `nix-daemon` is listening on [`DAEMON_SOCKET`]
+///
+/// A running daemon is needed by every shell, so this is run once rather than per shell.
+#[tracing::instrument(skip_all)]
+pub async fn daemon_self_test(timeout: Duration) -> Result<(), SelfTestError> {
+    let path = PathBuf::from(DAEMON_SOCKET);
+    let connected =
+        match tokio::time::timeout(timeout, tokio::net::UnixStream::connect(&path)).await {
+            Ok(connected) => connected.map(drop),
+            Err(_) => Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                format!("Timed out after {}s", timeout.as_secs_f32()),
+            )),
+        };
+    connected.map_err(|error| SelfTestError::DaemonUnreachable { path, error })
+}
+
+/// Like [`self_test_report`], but tests exactly the given `shells` instead of discovering them
+///
+/// If `flakes` is set, the new `nix` CLI is verified instead of `nix-env`. Otherwise, if [`channels_installed`], `nixpkgs` is verified to be on the Nix search path as well.
+/// Each shell is tested both as a login shell and, with [`Shell::interactive_self_test`], as an interactive one, then checked with [`Shell::ssl_cert_self_test`] and [`Shell::shadowing_self_test`]. If [`daemon_installed`], the [`daemon_self_test`] is run once as well.
+/// Every command run is given `timeout` to complete.
+#[tracing::instrument(skip_all)]
+pub async fn self_test_report_with(
+    shells: &[Shell],
+    flakes: bool,
+    timeout: Duration,
+) -> SelfTestReport {
+    self_test_report_with_checks(shells, flakes, timeout, &[]).await
+}
+
+/// Like [`self_test_report_with`], but also runs the operator's own `checks` once the built-in tests are done
+///
+/// Each check runs in the shell it names, whether or not that shell is among `shells`.
+#[tracing::instrument(skip_all)]
+pub async fn self_test_report_with_checks(
+    shells: &[Shell],
+    flakes: bool,
+    timeout: Duration,
+    checks: &[CustomCheck],
+) -> SelfTestReport {
+    let check_nix_path = !flakes && channels_installed();
+    let daemon_tested = daemon_installed();
+    let daemon_failure = if daemon_tested {
+        daemon_self_test(timeout).await.err()
+    } else {
+        None
+    };
+    let mut set = JoinSet::new();
+    let mut task_shells = HashMap::new();
+    for (idx, shell) in shells.iter().copied().enumerate() {
+        let abort_handle = set.spawn(async move {
+            shell.self_test(timeout, flakes).await?;
+            shell.interactive_self_test(timeout, flakes).await?;
+            shell.ssl_cert_self_test(timeout).await?;
+            shell.shadowing_self_test(timeout).await?;
+            if check_nix_path {
+                shell.nix_path_self_test(timeout).await?;
+            }
+            Ok(())
+        });
+        task_shells.insert(abort_handle.id(), (idx, shell));
+    }
+
+    let mut reports = vec![];
+    while let Some(result) = set.join_next_with_id().await {
+        let (id, result) = match result {
+            Ok((id, result)) => (id, result),
+            Err(error) => {
+                let (_, shell) = task_shells[&error.id()];
+                (error.id(), Err(SelfTestError::Join { shell, error }))
+            },
+        };
+        let (idx, shell) = task_shells[&id];
+        reports.push((idx, ShellReport::new(shell, result)));
+    }
+    reports.sort_by_key(|(idx, _)| *idx);
+
+    let mut custom_checks = vec![];
+    for check in checks {
+        let result = check.shell.custom_check(check, timeout).await;
+        custom_checks.push(CustomCheckReport {
+            name: check.name.clone(),
+            shell: check.shell,
+            passed: result.is_ok(),
+            error: result.as_ref().err().map(|err| err.to_string()),
+            failure: result.err(),
+        });
+    }
+
+    SelfTestReport {
+        shells: reports.into_iter().map(|(_, report)| report).collect(),
+        daemon_error: daemon_failure.as_ref().map(|err| err.to_string()),
+        daemon_failure,
+        daemon_tested,
+        custom_checks,
+    }
+}
+
+/// Test the Nix install in every discovered shell
+///
+/// Skipped if [`NO_SELF_TEST_ENV`] is set, such as in containers which cannot spawn shells.
 #[tracing::instrument(skip_all)]
 pub async fn self_test() -> Result<(), Vec<SelfTestError>> {
+    self_test_summarized().await.1
+}
+
+/// Like [`self_test`], but also counts how many shells passed, failed, or were skipped
+#[tracing::instrument(skip_all)]
+pub async fn self_test_summarized() -> (SelfTestSummary, Result<(), Vec<SelfTestError>>) {
     let shells = Shell::discover();
+    if self_test_disabled() {
+        tracing::debug!("Skipping self-test, `{NO_SELF_TEST_ENV}` is set");
+        let summary = SelfTestSummary {
+            skipped: Shell::all().len(),
+            ..Default::default()
+        };
+        return (summary, Ok(()));
+    }
+    let report = self_test_report_with(&shells, flakes_enabled(), DEFAULT_SELF_TEST_TIMEOUT).await;
+    (report.summary(), report.into_result())
+}
+
+/// Like [`self_test`], but also runs the operator's own `checks`, see [`self_test_report_with_checks`]
+#[tracing::instrument(skip_all)]
+pub async fn self_test_with_checks(checks: &[CustomCheck]) -> Result<(), Vec<SelfTestError>> {
+    if self_test_disabled() {
+        tracing::debug!("Skipping self-test, `{NO_SELF_TEST_ENV}` is set");
+        return Ok(());
+    }
+    self_test_report_with_checks(
+        &Shell::discover(),
+        flakes_enabled(),
+        DEFAULT_SELF_TEST_TIMEOUT,
+        checks,
+    )
+    .await
+    .into_result()
+}
+
+fn self_test_disabled() -> bool {
+    std::env::var(NO_SELF_TEST_ENV).is_ok_and(|value| !matches!(value.trim(), "" | "0" | "false"))
+}
 
-    let mut failures = vec![];
+/// Like [`self_test`], but tests exactly the given `shells` instead of discovering them
+#[tracing::instrument(skip_all)]
+pub async fn self_test_with(shells: &[Shell]) -> Result<(), Vec<SelfTestError>> {
+    if self_test_disabled() {
+        tracing::debug!("Skipping self-test, `{NO_SELF_TEST_ENV}` is set");
+        return Ok(());
+    }
+    self_test_report_with(shells, flakes_enabled(), DEFAULT_SELF_TEST_TIMEOUT)
+        .await
+        .into_result()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn reports_when_no_shells_were_tested() {
+        let report = self_test_report_with(&[], false, DEFAULT_SELF_TEST_TIMEOUT).await;
+        assert!(report.is_empty());
+        assert_eq!(report.tested(), 0);
+        assert!(report.passed());
+        let summary = report.summary();
+        assert_eq!(summary.skipped, Shell::all().len());
+        assert_eq!(summary.tested, usize::from(report.daemon_tested));
+    }
 
-    for shell in shells {
-        match shell.self_test().await {
-            Ok(()) => (),
-            Err(err) => failures.push(err),
+    #[tokio::test]
+    async fn runs_custom_checks() {
+        let passing = CustomCheck::new("exits", Shell::Sh, "exit 3").expected_exit_code(3);
+        Shell::Sh
+            .custom_check(&passing, DEFAULT_SELF_TEST_TIMEOUT)
+            .await
+            .unwrap();
+
+        let failing = CustomCheck::new("fails", Shell::Sh, "echo broken >&2; exit 1");
+        let err = Shell::Sh
+            .custom_check(&failing, DEFAULT_SELF_TEST_TIMEOUT)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, SelfTestError::CustomCheckFailed { name, expected_exit_code: 0, .. } if name == "fails")
+        );
+        assert_eq!(err.stderr_lossy().as_deref(), Some("broken\n"));
+    }
+
+    #[test]
+    fn detects_shadowed_nix() {
+        assert_eq!(
+            shadowed_nix(Shell::Bash, "/nix/var/nix/profiles/default/bin/nix\n"),
+            None
+        );
+        assert_eq!(
+            shadowed_nix(Shell::Bash, "Welcome!\nalias nix='nix --offline'\n").as_deref(),
+            Some("alias nix='nix --offline'")
+        );
+        assert_eq!(shadowed_nix(Shell::Zsh, "nix\n").as_deref(), Some("nix"));
+        assert_eq!(shadowed_nix(Shell::Fish, "file\n"), None);
+        assert_eq!(
+            shadowed_nix(Shell::Fish, "function\n").as_deref(),
+            Some("function")
+        );
+    }
+
+    #[test]
+    fn summarizes_every_check() {
+        let report = SelfTestReport {
+            shells: vec![ShellReport::new(Shell::Bash, Ok(()))],
+            daemon_error: None,
+            daemon_failure: None,
+            daemon_tested: true,
+            custom_checks: vec![CustomCheckReport {
+                name: "fails".into(),
+                shell: Shell::Sh,
+                passed: false,
+                error: None,
+                failure: None,
+            }],
+        };
+        assert_eq!(
+            report.summary(),
+            SelfTestSummary {
+                tested: 3,
+                passed: 2,
+                failed: 1,
+                skipped: Shell::all().len() - 1,
+            }
+        );
+    }
+
+    #[test]
+    fn displays_summary_counts() {
+        let summary = SelfTestSummary {
+            tested: 4,
+            passed: 3,
+            failed: 1,
+            skipped: 0,
+        };
+        assert_eq!(summary.to_string(), "3/4 checks passed");
+        let skipped = SelfTestSummary {
+            skipped: 2,
+            ..Default::default()
+        };
+        assert_eq!(skipped.to_string(), "0/0 checks passed, 2 shells skipped");
+    }
+
+    #[tokio::test]
+    async fn reports_unreachable_daemon() {
+        if Path::new(DAEMON_SOCKET).exists() {
+            return;
         }
+        let err = daemon_self_test(Duration::from_secs(1)).await.unwrap_err();
+        assert!(matches!(err, SelfTestError::DaemonUnreachable { .. }));
+    }
+
+    #[test]
+    fn classifies_shells_by_file_name() {
+        assert_eq!(
+            Shell::from_path(Path::new("/usr/local/bin/bash")),
+            Some(Shell::Bash)
+        );
+        assert_eq!(Shell::from_path(Path::new("nu")), Some(Shell::Nu));
+        assert_eq!(
+            Shell::from_path(Path::new("/usr/bin/pwsh")),
+            Some(Shell::Pwsh)
+        );
+        assert_eq!(Shell::from_path(Path::new("/nonexistent/tcsh")), None);
+    }
+
+    #[test]
+    fn quotes_commands_for_copy_pasting() {
+        let argv = ["bash", "-l", "-c", "exec nix-env --version", "it's", ""].map(String::from);
+        assert_eq!(
+            shell_quote(&argv),
+            r#"bash -l -c 'exec nix-env --version' 'it'\''s' ''"#
+        );
+    }
+
+    #[test]
+    fn selects_nix_command_for_flakes() {
+        assert_eq!(
+            Shell::Bash
+                .self_test_command(SelfTestMode::Login, false)
+                .last()
+                .unwrap(),
+            "exec nix-env --version"
+        );
+        assert_eq!(
+            Shell::Bash
+                .self_test_command(SelfTestMode::Login, true)
+                .last()
+                .unwrap(),
+            r#"exec nix --extra-experimental-features "nix-command flakes" flake --version"#
+        );
+    }
+
+    #[test]
+    fn checks_fish_finds_nix() {
+        assert_eq!(
+            Shell::Fish
+                .self_test_command(SelfTestMode::Login, false)
+                .last()
+                .unwrap(),
+            "type -q nix-env; and exec nix-env --version"
+        );
+    }
+
+    #[test]
+    fn prefers_nix_ssl_cert_file() {
+        assert_eq!(
+            ssl_cert_file_from_env(
+                "SSL_CERT_FILE=/etc/ssl/cert.pem\nNIX_SSL_CERT_FILE=/etc/ssl/certs/ca-certificates.crt\n"
+            ),
+            Some(PathBuf::from("/etc/ssl/certs/ca-certificates.crt"))
+        );
+        assert_eq!(
+            ssl_cert_file_from_env("NIX_SSL_CERT_FILE=\nSSL_CERT_FILE=/etc/ssl/cert.pem\n"),
+            Some(PathBuf::from("/etc/ssl/cert.pem"))
+        );
+        assert_eq!(ssl_cert_file_from_env("PATH=/bin\n"), None);
+    }
+
+    #[test]
+    fn runs_commands_in_c_locale() {
+        let command = c_locale_command("sh");
+        let envs = command.as_std().get_envs().collect::<Vec<_>>();
+        assert!(envs.contains(&("LC_ALL".as_ref(), Some("C".as_ref()))));
+        assert!(envs.contains(&("LANG".as_ref(), Some("C".as_ref()))));
+        assert!(envs.contains(&("LANGUAGE".as_ref(), None)));
     }
 
-    if failures.is_empty() {
+    #[test]
+    fn notes_replaced_stderr_bytes() {
+        assert_eq!(display_stderr(b"not found"), "not found");
+        let rendered = display_stderr(b"bad \xff byte");
+        assert!(rendered.starts_with("bad \u{FFFD} byte\n"));
+        assert!(rendered.contains("not valid UTF-8"));
+    }
+
+    #[test]
+    fn runs_pwsh_without_its_profile() {
+        let argv = Shell::Pwsh.self_test_command(SelfTestMode::Login, false);
+        assert_eq!(argv[1..3], ["-NoProfile", "-Command"]);
+        assert!(argv[3].ends_with("nix-env --version; exit $LASTEXITCODE"));
+    }
+
+    #[test]
+    fn starts_interactive_shells_without_login() {
+        let argv = Shell::Bash.self_test_command(SelfTestMode::Interactive, false);
+        assert_eq!(argv[1..3], ["-i", "-c"]);
+    }
+
+    #[test]
+    fn skips_interactive_shells_without_a_profile() {
+        assert!(!Shell::Sh.sources_interactive_profile());
+        assert_eq!(
+            Shell::Bash.sources_interactive_profile(),
+            !cfg!(target_os = "macos")
+        );
+        assert!(Shell::Zsh.sources_interactive_profile());
+    }
+
+    #[tokio::test]
+    async fn passes_interactive_self_test_of_sh_without_running_it() {
+        Shell::Sh
+            .interactive_self_test(Duration::from_millis(1), false)
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn resolves_nixpkgs_after_sourcing_profile() {
+        assert_eq!(
+            Shell::Zsh.nix_path_command().last().unwrap(),
+            "exec nix-instantiate --find-file nixpkgs"
+        );
+    }
+
+    #[test]
+    fn prints_login_path_without_sourcing_profile() {
+        assert_eq!(
+            Shell::Bash.login_path_command()[1..],
+            ["-l", "-i", "-c", "exec printenv PATH"]
+        );
+        assert_eq!(
+            Shell::Nu.login_path_command().last().unwrap(),
+            "^printenv PATH"
+        );
+    }
+
+    #[test]
+    fn classifies_shells_through_symlinks() -> eyre::Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let target = temp_dir.path().join("zsh");
+        std::fs::write(&target, "")?;
+        let link = temp_dir.path().join("my-login-shell");
+        std::os::unix::fs::symlink(&target, &link)?;
+
+        assert_eq!(Shell::from_path(&link), Some(Shell::Zsh));
         Ok(())
-    } else {
-        Err(failures)
     }
 }
@@ -187,6 +187,11 @@ pub struct CommonSettings {
     #[cfg_attr(feature = "cli", clap(long, env = "NIX_INSTALLER_SSL_CERT_FILE"))]
     pub ssl_cert_file: Option<PathBuf>,
 
+    /// How many seconds HTTP requests (such as fetching Nix) may take before giving up, unlimited if unset
+    #[cfg_attr(feature = "cli", clap(long, env = "NIX_INSTALLER_HTTP_TIMEOUT"))]
+    #[serde(default)]
+    pub http_timeout: Option<u64>,
+
     /// Extra configuration lines for `/etc/nix.conf`
     #[cfg_attr(feature = "cli", clap(long, action = ArgAction::Append, num_args = 0.., env = "NIX_INSTALLER_EXTRA_CONF", global = true))]
     pub extra_conf: Vec<UrlOrPathOrString>,
@@ -313,6 +318,7 @@ impl CommonSettings {
             extra_conf: Default::default(),
             force: false,
             ssl_cert_file: Default::default(),
+            http_timeout: Default::default(),
             #[cfg(feature = "diagnostics")]
             diagnostic_attribution: None,
             #[cfg(feature = "diagnostics")]
@@ -334,6 +340,7 @@ impl CommonSettings {
             extra_conf,
             force,
             ssl_cert_file,
+            http_timeout,
             #[cfg(feature = "diagnostics")]
                 diagnostic_attribution: _,
             #[cfg(feature = "diagnostics")]
@@ -371,6 +378,7 @@ impl CommonSettings {
         );
         map.insert("proxy".into(), serde_json::to_value(proxy)?);
         map.insert("ssl_cert_file".into(), serde_json::to_value(ssl_cert_file)?);
+        map.insert("http_timeout".into(), serde_json::to_value(http_timeout)?);
         map.insert("extra_conf".into(), serde_json::to_value(extra_conf)?);
         map.insert("force".into(), serde_json::to_value(force)?);
 